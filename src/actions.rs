@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+use bevy::ecs::system::SystemState;
+use serde::{Deserialize, Serialize};
+
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        // Live input is applied at the end of the frame it was queued on, so the colony reacts
+        // while paused too. It still lands before the next tick's boundary, which is where a
+        // replay feeds it back in
+        app.init_resource::<PlayerActions>()
+            .add_systems(
+                TickBoundary,
+                apply_player_actions.in_set(ApplyActions).before(GridSync),
+            )
+            .add_systems(PostUpdate, apply_player_actions.in_set(ApplyActions));
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApplyActions;
+
+// Everything the player does to the colony, queued by the input systems and applied between
+// ticks so it lands on the same tick of the simulation every run
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PlayerAction {
+    // Cells are where each building of the drag is anchored
+    Build {
+        mode: ClickMode,
+        rotation: Rotation,
+        cells: Vec<GridLocation>,
+    },
+    Designate {
+        cells: Vec<GridLocation>,
+        designate: bool,
+    },
+    // Moves whatever covers `grabbed` so that cell ends up on `dropped`
+    Move {
+        grabbed: GridLocation,
+        dropped: GridLocation,
+    },
+    Undo,
+    Redo,
+    AnswerArrival(bool),
+    Explode(GridLocation),
+    // A hot reload of the active scenario
+    LoadScenario(Box<Scenario>),
+}
+
+#[derive(Resource, Default)]
+pub struct PlayerActions {
+    queued: VecDeque<PlayerAction>,
+    // Every action applied so far and the tick it landed on, which is all a replay needs
+    applied: Vec<(u64, PlayerAction)>,
+    // Live input is dropped while a replay feeds the actions
+    replaying: bool,
+}
+
+impl PlayerActions {
+    pub(crate) fn replaying() -> Self {
+        Self {
+            replaying: true,
+            ..default()
+        }
+    }
+
+    pub fn push(&mut self, action: PlayerAction) {
+        if !self.replaying {
+            self.queued.push_back(action);
+        }
+    }
+
+    pub(crate) fn push_replayed(&mut self, action: PlayerAction) {
+        self.queued.push_back(action);
+    }
+
+    // Hands control back to the player once a replay runs out
+    pub(crate) fn stop_replaying(&mut self) {
+        self.replaying = false;
+    }
+
+    pub fn applied(&self) -> &[(u64, PlayerAction)] {
+        &self.applied
+    }
+}
+
+// Every queued action, syncing the grids after each so the next one sees it placed
+fn apply_player_actions(
+    world: &mut World,
+    state: &mut SystemState<(
+        Editor,
+        ResMut<Population>,
+        ResMut<Explosions>,
+        Option<ResMut<ActiveScenario>>,
+    )>,
+) {
+    loop {
+        let tick = world.resource::<SimClock>().tick();
+        let mut actions = world.resource_mut::<PlayerActions>();
+        let action = match actions.queued.pop_front() {
+            Some(action) => action,
+            None => return,
+        };
+        actions.applied.push((tick, action.clone()));
+
+        let (mut editor, mut population, mut explosions, scenario) = state.get_mut(world);
+        match action {
+            PlayerAction::Build {
+                mode,
+                rotation,
+                cells,
+            } => editor.build(mode, rotation, cells),
+            PlayerAction::Designate { cells, designate } => editor.designate(&cells, designate),
+            PlayerAction::Move { grabbed, dropped } => editor.move_building(&grabbed, &dropped),
+            PlayerAction::Undo => editor.undo(),
+            PlayerAction::Redo => editor.redo(),
+            PlayerAction::AnswerArrival(accepted) => population.answer(accepted),
+            PlayerAction::Explode(location) => explosions.queue(Explosion::small(location)),
+            PlayerAction::LoadScenario(loaded) => {
+                if let Some(mut scenario) = scenario {
+                    scenario.load(*loaded);
+                }
+            }
+        }
+        state.apply(world);
+        sync_grids(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colony(seed: u64) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Input<KeyCode>>()
+            // DurabilityPlugin draws with gizmos, which need a renderer
+            .init_resource::<Explosions>()
+            .add_event::<DamageEvent>()
+            .insert_resource(SimRng::new(seed))
+            .add_plugins((
+                SimClockPlugin,
+                BuildingPlugin,
+                HistoryPlugin,
+                ActionPlugin,
+                AiPlugin,
+                NeedsPlugin,
+                MoodPlugin,
+                PathfindingPlugin,
+                PopulationPlugin,
+                HealthPlugin,
+            ));
+        app.world.resource_mut::<SimClock>().paused = true;
+        let mut state = SystemState::<(Commands, ResMut<SimRng>)>::new(&mut app.world);
+        let (mut commands, mut rng) = state.get_mut(&mut app.world);
+        for offset in 0..3 {
+            let position = spawn_point().as_vec2() + Vec2::new(offset as f32, 0.0);
+            spawn_pawn(&mut commands, &mut rng, position);
+        }
+        state.apply(&mut app.world);
+        app
+    }
+
+    fn step(app: &mut App, ticks: usize) {
+        for _ in 0..ticks {
+            app.world.resource_mut::<SimClock>().step();
+            app.update();
+        }
+    }
+
+    fn snapshot(app: &mut App) -> Vec<String> {
+        let mut state = Vec::new();
+        let mut pawns = app
+            .world
+            .query_filtered::<(&Transform, &Hunger, &Brain), With<Pawn>>();
+        for (transform, hunger, brain) in pawns.iter(&app.world) {
+            state.push(format!(
+                "{:?} {} {:?}",
+                transform.translation,
+                hunger.value,
+                brain.state()
+            ));
+        }
+        let mut structures = app
+            .world
+            .query_filtered::<(&GridLocation, &Durability), With<Structure>>();
+        let mut structures = structures
+            .iter(&app.world)
+            .map(|(location, durability)| format!("{:?} {}", location, durability.health))
+            .collect::<Vec<_>>();
+        structures.sort();
+        state.extend(structures);
+        state.push(format!("{}", app.world.resource::<Stockpile>().materials));
+        state
+    }
+
+    fn wall(x: i32) -> PlayerAction {
+        let spawn = spawn_point();
+        PlayerAction::Build {
+            mode: ClickMode::BuildWall,
+            rotation: Rotation::default(),
+            cells: (0..4)
+                .map(|y| GridLocation(spawn.0 + IVec2::new(x, y + 3)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn every_queued_action_applies_while_paused() {
+        let mut app = colony(1);
+        {
+            let mut actions = app.world.resource_mut::<PlayerActions>();
+            actions.push(wall(2));
+            actions.push(wall(4));
+            actions.push(PlayerAction::Undo);
+        }
+        app.update();
+        let actions = app.world.resource::<PlayerActions>();
+        assert_eq!(actions.applied().len(), 3);
+        assert!(actions.applied().iter().all(|(tick, _)| *tick == 0));
+        assert_eq!(app.world.resource::<SimClock>().tick(), 0);
+        let mut walls = app.world.query_filtered::<(), With<Wall>>();
+        assert_eq!(walls.iter(&app.world).count(), 4);
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_colony() {
+        let run = || {
+            let mut app = colony(9);
+            step(&mut app, 30);
+            app.world.resource_mut::<PlayerActions>().push(wall(2));
+            step(&mut app, 200);
+            {
+                let mut actions = app.world.resource_mut::<PlayerActions>();
+                actions.push(wall(-3));
+                actions.push(PlayerAction::Designate {
+                    cells: vec![GridLocation(spawn_point().0 + IVec2::new(2, 3))],
+                    designate: true,
+                });
+            }
+            step(&mut app, 600);
+            snapshot(&mut app)
+        };
+        assert_eq!(run(), run());
+    }
+}
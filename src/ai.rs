@@ -1,12 +1,13 @@
 use crate::prelude::*;
-use bevy::utils::FloatOrd;
+use bevy::utils::{FloatOrd, HashSet};
+use rand::Rng;
 
 pub struct AiPlugin;
 
 impl Plugin for AiPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
+            SimulationTick,
             (
                 wander,
                 update_brains,
@@ -14,6 +15,12 @@ impl Plugin for AiPlugin {
                 get_food,
                 clear_path_if_dirty,
                 operate_food_machine,
+                find_structure_job,
+                work_on_structure,
+                find_patient,
+                feed_patient,
+                // Tantrum damage lands on the tick it's dealt
+                mental_break.before(apply_damage),
             ),
         );
     }
@@ -22,44 +29,175 @@ impl Plugin for AiPlugin {
 #[derive(Component)]
 pub struct Pawn;
 
+// Name, traits and skills are rolled from the Identity stream
+pub fn spawn_pawn(commands: &mut Commands, rng: &mut SimRng, position: Vec2) -> Entity {
+    let rng = rng.stream(RngStream::Identity);
+    let identity = (random_name(rng), random_traits(rng), random_skills(rng));
+    spawn_pawn_with_identity(commands, position, identity)
+}
+
+// For pawns whose identity was rolled earlier, like arrivals
+pub fn spawn_pawn_with_identity(
+    commands: &mut Commands,
+    position: Vec2,
+    (name, traits, skills): (Name, Traits, Skills),
+) -> Entity {
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(800.0))),
+            CharacterSprite::default(),
+            Pawn,
+            LastDirection(Vec2::ZERO),
+            AnimationTimer(Timer::from_seconds(0.2, TimerMode::Repeating)),
+            Brain::default(),
+            AiPath::default(),
+            Hunger { value: 100.0 },
+            Recreation { value: 100.0 },
+            Health { value: MAX_HEALTH },
+            Mood::default(),
+            name,
+            traits,
+            skills,
+        ))
+        .id()
+}
+
 #[derive(Component, Default)]
 pub struct Brain {
     state: BrainState,
 }
 
+impl Brain {
+    pub fn state(&self) -> &BrainState {
+        &self.state
+    }
+}
+
+#[derive(Debug)]
 pub enum BrainState {
     Wander(f32),
     GetFood,
     OperateMachine(Entity),
+    Repair(Entity),
+    Deconstruct(Entity),
     Relax,
+    // Too hurt to move, see INCAPACITATED_HEALTH
+    Incapacitated,
+    // Sharing food with a collapsed pawn until it can stand again
+    Feed(Entity),
+    // Mental breaks, seconds left. Sulking pawns stand still, tantrums damage whatever is next to them
+    Sulk(f32),
+    Tantrum(f32),
 }
 
+impl BrainState {
+    // The structure being worked on, if this is a structure job
+    pub fn job_target(&self) -> Option<Entity> {
+        match self {
+            BrainState::Repair(target) | BrainState::Deconstruct(target) => Some(*target),
+            _ => None,
+        }
+    }
+}
+
+const REPAIR_RATE: f32 = 2.0;
+const DECONSTRUCT_RATE: f32 = 1.0;
+const BREAK_DURATION: f32 = 20.0;
+const FINE_MEAL_QUALITY: f32 = 0.5;
+const TANTRUM_DAMAGE: f32 = 1.0;
+const FEED_RATE: f32 = 10.0;
+// Feeders keep at least this much for themselves, well clear of starving
+const SPARE_FOOD: f32 = 60.0;
+// Patients are topped up to this, they heal on any food so it only has to last
+const PATIENT_FED: f32 = 50.0;
+
 impl Default for BrainState {
     fn default() -> Self {
         BrainState::Wander(0.0)
     }
 }
 
-fn update_brains(mut brains: Query<(&mut Brain, &mut TextureAtlasSprite, &Hunger, &Recreation)>) {
-    for (mut brain, mut sprite, hunger, _recreation) in &mut brains {
+fn update_brains(
+    mut commands: Commands,
+    mut brains: Query<(
+        Entity,
+        &mut Brain,
+        &mut AiPath,
+        &mut TextureAtlasSprite,
+        &Hunger,
+        &Recreation,
+        &Health,
+        &Mood,
+    )>,
+    mut rng: ResMut<SimRng>,
+) {
+    let rng = rng.stream(RngStream::Mood);
+    for (pawn, mut brain, mut path, mut sprite, hunger, recreation, health, mood) in &mut brains {
         sprite.color = Color::WHITE;
+        if health.value < INCAPACITATED_HEALTH {
+            if !matches!(brain.state, BrainState::Incapacitated) {
+                brain.state = BrainState::Incapacitated;
+                path.locations.clear();
+                commands.entity(pawn).remove::<PathfindingTask>();
+            }
+            sprite.color = Color::GRAY;
+            continue;
+        }
+
+        // Eating and getting food come before any break, a starving pawn that sulks instead
+        // only gets hungrier and unhappier
         if matches!(brain.state, BrainState::OperateMachine(_)) {
             sprite.color = Color::GREEN;
             continue;
         }
+        let starving = hunger.value < 40.0;
 
-        if hunger.value < 40.0 {
+        match brain.state {
+            BrainState::Sulk(_) if !starving => {
+                sprite.color = Color::PURPLE;
+                continue;
+            }
+            BrainState::Tantrum(_) if !starving => {
+                sprite.color = Color::RED;
+                continue;
+            }
+            _ if !starving && mood.level() == MoodLevel::Breaking => {
+                brain.state = if rng.gen_bool(0.5) {
+                    BrainState::Sulk(BREAK_DURATION)
+                } else {
+                    BrainState::Tantrum(BREAK_DURATION)
+                };
+                path.locations.clear();
+                commands.entity(pawn).remove::<PathfindingTask>();
+                continue;
+            }
+            _ => {}
+        }
+
+        if starving {
             brain.state = BrainState::GetFood;
             sprite.color = Color::ORANGE;
             continue;
         }
-        /*
-        if recreation.value < 0.4 {
-            brain.state = BrainState::Relax;
+
+        if brain.state.job_target().is_some() && !mood.refuses_work() {
+            sprite.color = Color::CYAN;
+            continue;
+        }
+        if matches!(brain.state, BrainState::Feed(_)) {
+            sprite.color = Color::YELLOW;
+            continue;
+        }
+        let relaxing = matches!(brain.state, BrainState::Relax);
+        if recreation.value < RELAX_BELOW || (relaxing && recreation.value < 100.0) {
+            if !relaxing {
+                brain.state = BrainState::Relax;
+                path.locations.clear();
+                commands.entity(pawn).remove::<PathfindingTask>();
+            }
             sprite.color = Color::BLUE;
             continue;
         }
-        */
 
         if !matches!(brain.state, BrainState::Wander(_)) {
             sprite.color = Color::WHITE;
@@ -69,11 +207,14 @@ fn update_brains(mut brains: Query<(&mut Brain, &mut TextureAtlasSprite, &Hunger
 }
 
 fn operate_food_machine(
-    mut brains: Query<(&mut Brain, &mut Hunger), Without<PathfindingTask>>,
+    mut brains: Query<
+        (&mut Brain, &mut Hunger, &mut Mood, &mut Skills, &Name),
+        Without<PathfindingTask>,
+    >,
     foods: Query<&FoodMachine>,
-    time: Res<Time>,
+    clock: Res<SimClock>,
 ) {
-    for (mut brain, mut hunger) in &mut brains {
+    for (mut brain, mut hunger, mut mood, mut skills, name) in &mut brains {
         let machine = match &brain.state {
             BrainState::OperateMachine(val) => val,
             _ => continue,
@@ -88,8 +229,25 @@ fn operate_food_machine(
             }
         };
 
-        hunger.value += food.rate * time.delta_seconds();
+        // Only meals a pawn cooks itself train cooking, machines hand out the same food to everyone
+        let speed = if food.cooked {
+            if let Some(level) = skills.practice(SkillKind::Cooking, clock.delta_seconds()) {
+                info!("{} reached cooking level {}", name, level);
+            }
+            skills.cooking.speed()
+        } else {
+            1.0
+        };
+        hunger.value += food.rate * speed * clock.delta_seconds();
         if hunger.value >= 100.0 {
+            // Good cooks make meals worth remembering
+            mood.think(
+                if food.cooked && skills.cooking.quality() >= FINE_MEAL_QUALITY {
+                    Thought::AteFineMeal
+                } else {
+                    Thought::AteRecently
+                },
+            );
             brain.state = BrainState::default();
         }
     }
@@ -98,10 +256,10 @@ fn operate_food_machine(
 fn get_food(
     mut commands: Commands,
     mut brains: Query<(Entity, &AiPath, &mut Brain, &Transform), Without<PathfindingTask>>,
-    walls: Res<Grid<Wall>>,
-    machine_grid: Res<Grid<Machine>>,
-    components: Res<ConnectedComponents<Wall>>,
-    food: Query<&Machine, With<FoodMachine>>,
+    structures: Res<Grid<Structure>>,
+    components: Res<ConnectedComponents<Structure>>,
+    food: Query<(Entity, &Machine, &GridLocation), With<FoodMachine>>,
+    clock: Res<SimClock>,
 ) {
     for (target, path, mut brain, transform) in &mut brains {
         if !matches!(brain.state, BrainState::GetFood) {
@@ -117,12 +275,12 @@ fn get_food(
         };
 
         //FIXME should find closest machine, or better one that can be path found to
-        let (machine_entity, target_point) = match machine_grid
+        let (machine_entity, target_point) = match food
             .iter()
-            .filter(|(ent, _)| food.get(*ent).is_ok())
-            .map(|(ent, location)| (ent, food.get(ent).unwrap(), location))
-            .map(|(ent, machine, location)| {
-                (ent, GridLocation::from(location.0 + machine.use_offset))
+            .flat_map(|(ent, machine, location)| {
+                machine
+                    .use_locations(location)
+                    .map(move |use_location| (ent, use_location))
             })
             .filter(|(_ent, location)| components.in_same_component(location, &brain_location))
             .min_by_key(|(_, location)| {
@@ -152,8 +310,9 @@ fn get_food(
             } else {
                 spawn_optimized_pathfinding_task(
                     &mut commands,
+                    &clock,
                     target,
-                    &walls,
+                    &structures,
                     brain_location,
                     target_point,
                 );
@@ -162,11 +321,10 @@ fn get_food(
     }
 }
 
+// Searches still running are checked against the chunks they cross when they finish
 fn clear_path_if_dirty(
-    mut commands: Commands,
-    mut dirty: EventReader<DirtyGridEvent<Wall>>,
+    mut dirty: EventReader<DirtyGridEvent<Structure>>,
     mut brains: Query<&mut AiPath, Without<PathfindingTask>>,
-    mut pathfinding: Query<Entity, With<PathfindingTask>>,
 ) {
     for event in dirty.iter() {
         for mut path in &mut brains {
@@ -178,35 +336,33 @@ fn clear_path_if_dirty(
                 path.locations.clear();
             }
         }
-        for entity in &mut pathfinding {
-            commands.entity(entity).remove::<PathfindingTask>();
-        }
     }
 }
 
 fn wander(
     mut commands: Commands,
     mut brains: Query<(Entity, &AiPath, &mut Brain, &Transform), Without<PathfindingTask>>,
-    time: Res<Time>,
-    walls: Res<Grid<Wall>>,
-    wall_connected: Res<ConnectedComponents<Wall>>,
+    clock: Res<SimClock>,
+    structures: Res<Grid<Structure>>,
+    structure_connected: Res<ConnectedComponents<Structure>>,
+    mut rng: ResMut<SimRng>,
 ) {
+    let rng = rng.stream(RngStream::Wander);
     for (target, path, mut brain, transform) in &mut brains {
         if let BrainState::Wander(last_wander_time) = &mut brain.state {
-            *last_wander_time += time.delta_seconds();
+            *last_wander_time += clock.delta_seconds();
             if *last_wander_time > 1.0 && path.locations.is_empty() {
                 *last_wander_time = 0.0;
 
-                let mut rng = rand::thread_rng();
-
                 if let Some(start) = GridLocation::from_world(transform.translation.truncate()) {
                     if let Some(end) =
-                        wall_connected.random_point_in_same_component(&start, &mut rng)
+                        structure_connected.random_point_in_same_component(&start, rng)
                     {
                         spawn_optimized_pathfinding_task(
                             &mut commands,
+                            &clock,
                             target,
-                            &walls,
+                            &structures,
                             start,
                             end.clone(),
                         );
@@ -223,13 +379,13 @@ fn wander(
 
 // Does this need to read global transform
 fn follow_path(
-    mut paths: Query<(&mut Transform, &mut AiPath, &mut LastDirection)>,
-    time: Res<Time>,
+    mut paths: Query<(&mut Transform, &mut AiPath, &mut LastDirection, &Traits)>,
+    clock: Res<SimClock>,
 ) {
-    for (mut transform, mut path, mut last_direction) in &mut paths {
+    for (mut transform, mut path, mut last_direction, traits) in &mut paths {
         if let Some(next_target) = path.locations.front() {
             let delta = *next_target - transform.translation.truncate();
-            let travel_amount = time.delta_seconds();
+            let travel_amount = clock.delta_seconds() * traits.walk_speed();
 
             if delta.length() > travel_amount * 1.1 {
                 let direction = delta.normalize().extend(0.0) * travel_amount;
@@ -243,3 +399,344 @@ fn follow_path(
         }
     }
 }
+
+// Walkable cells next to a structure where a pawn can stand to work on it
+fn work_tiles(
+    structures: &Grid<Structure>,
+    location: &GridLocation,
+    footprint: Option<&Footprint>,
+) -> Vec<GridLocation> {
+    let cells = covered_cells(location, footprint);
+    cells
+        .iter()
+        .flat_map(|cell| cell.neighbors())
+        .filter(|neighbor| !cells.contains(neighbor) && !structures.occupied(neighbor))
+        .collect()
+}
+
+// Deconstruction designations come first, then damaged structures
+fn find_structure_job(
+    mut brains: Query<(&mut Brain, &Transform, &Mood)>,
+    candidates: Query<(
+        Entity,
+        &Durability,
+        Option<&Deconstruct>,
+        &GridLocation,
+        Option<&Footprint>,
+    )>,
+    structures: Res<Grid<Structure>>,
+    components: Res<ConnectedComponents<Structure>>,
+) {
+    // Only one pawn works on each structure
+    let mut claimed = brains
+        .iter()
+        .filter_map(|(brain, _, _)| brain.state.job_target())
+        .collect::<HashSet<_>>();
+
+    for (mut brain, transform, mood) in &mut brains {
+        if !matches!(brain.state, BrainState::Wander(_)) || mood.refuses_work() {
+            continue;
+        }
+        let brain_location = match GridLocation::from_world(transform.translation.truncate()) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        let job = candidates
+            .iter()
+            .filter(|(entity, durability, deconstruct, _, _)| {
+                (deconstruct.is_some() || durability.damaged()) && !claimed.contains(entity)
+            })
+            .filter(|(_, _, _, location, footprint)| {
+                work_tiles(&structures, location, *footprint)
+                    .iter()
+                    .any(|tile| components.in_same_component(tile, &brain_location))
+            })
+            .min_by_key(|(_, _, deconstruct, location, _)| {
+                (
+                    deconstruct.is_none(),
+                    FloatOrd(
+                        transform
+                            .translation
+                            .truncate()
+                            .distance(location.as_vec2()),
+                    ),
+                )
+            });
+
+        if let Some((entity, _, deconstruct, _, _)) = job {
+            claimed.insert(entity);
+            brain.state = match deconstruct {
+                Some(_) => BrainState::Deconstruct(entity),
+                None => BrainState::Repair(entity),
+            };
+        }
+    }
+}
+
+fn work_on_structure(
+    mut commands: Commands,
+    mut brains: Query<
+        (
+            Entity,
+            &AiPath,
+            &mut Brain,
+            &Transform,
+            &Mood,
+            &Traits,
+            &mut Skills,
+            &Name,
+        ),
+        Without<PathfindingTask>,
+    >,
+    mut targets: Query<(
+        &mut Durability,
+        Option<&mut Deconstruct>,
+        Option<&BuildCost>,
+        &GridLocation,
+        Option<&Footprint>,
+    )>,
+    mut stockpile: ResMut<Stockpile>,
+    structures: Res<Grid<Structure>>,
+    components: Res<ConnectedComponents<Structure>>,
+    clock: Res<SimClock>,
+) {
+    let in_use = brains
+        .iter()
+        .filter_map(|(_, _, brain, ..)| match brain.state {
+            BrainState::OperateMachine(machine) => Some(machine),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    for (pawn, path, mut brain, transform, mood, traits, mut skills, name) in &mut brains {
+        let target = match brain.state.job_target() {
+            Some(target) => target,
+            None => continue,
+        };
+
+        let (mut durability, deconstruct, cost, location, footprint) = match targets.get_mut(target)
+        {
+            Ok(val) => val,
+            Err(_) => {
+                // Destroyed or removed while walking over
+                brain.state = BrainState::default();
+                continue;
+            }
+        };
+        let finished = match brain.state {
+            // Designation was cancelled
+            BrainState::Deconstruct(_) => deconstruct.is_none(),
+            _ => !durability.damaged(),
+        };
+        if finished {
+            brain.state = BrainState::default();
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let tiles = work_tiles(&structures, location, footprint);
+        if tiles
+            .iter()
+            .any(|tile| tile.as_vec2().distance(position) < 0.5)
+        {
+            let speed = mood.work_speed() * traits.work_speed() * skills.construction.speed();
+            match deconstruct {
+                Some(mut deconstruct) if matches!(brain.state, BrainState::Deconstruct(_)) => {
+                    // Wait for whoever is using the machine to finish
+                    if in_use.contains(&target) {
+                        continue;
+                    }
+                    deconstruct.progress += DECONSTRUCT_RATE * speed * clock.delta_seconds();
+                    if deconstruct.progress >= Deconstruct::WORK {
+                        stockpile.materials += cost.map_or(0, |cost| cost.refund());
+                        commands.entity(target).despawn_recursive();
+                        brain.state = BrainState::default();
+                    }
+                }
+                _ => {
+                    durability.health = (durability.health
+                        + REPAIR_RATE * speed * clock.delta_seconds())
+                    .min(durability.max);
+                }
+            }
+            if let Some(level) = skills.practice(SkillKind::Construction, clock.delta_seconds()) {
+                info!("{} reached construction level {}", name, level);
+            }
+            continue;
+        }
+
+        if path.locations.is_empty() {
+            let brain_location = match GridLocation::from_world(position) {
+                Some(val) => val,
+                None => {
+                    warn!("AI entity not in grid...");
+                    continue;
+                }
+            };
+            let closest = tiles
+                .into_iter()
+                .filter(|tile| components.in_same_component(tile, &brain_location))
+                .min_by_key(|tile| FloatOrd(tile.as_vec2().distance(position)));
+            match closest {
+                Some(end) => spawn_optimized_pathfinding_task(
+                    &mut commands,
+                    &clock,
+                    pawn,
+                    &structures,
+                    brain_location,
+                    end,
+                ),
+                None => brain.state = BrainState::default(),
+            }
+        }
+    }
+}
+
+// Collapsed pawns can't fetch food, so someone with food to spare brings theirs
+fn find_patient(
+    mut brains: Query<(Entity, &mut Brain, &Transform, &Hunger, &Health, &Mood)>,
+    components: Res<ConnectedComponents<Structure>>,
+) {
+    let patients = brains
+        .iter()
+        .filter(|(.., health, _)| health.value < INCAPACITATED_HEALTH)
+        .filter_map(|(patient, _, transform, ..)| {
+            GridLocation::from_world(transform.translation.truncate())
+                .map(|location| (patient, location))
+        })
+        .collect::<Vec<_>>();
+    if patients.is_empty() {
+        return;
+    }
+    // One feeder per patient
+    let mut claimed = brains
+        .iter()
+        .filter_map(|(_, brain, ..)| match brain.state {
+            BrainState::Feed(patient) => Some(patient),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    for (_, mut brain, transform, hunger, health, mood) in &mut brains {
+        if !matches!(brain.state, BrainState::Wander(_))
+            || mood.refuses_work()
+            || hunger.value <= SPARE_FOOD
+            || health.value < INCAPACITATED_HEALTH
+        {
+            continue;
+        }
+        let brain_location = match GridLocation::from_world(transform.translation.truncate()) {
+            Some(val) => val,
+            None => continue,
+        };
+        let patient = patients
+            .iter()
+            .filter(|(patient, location)| {
+                !claimed.contains(patient)
+                    && components.in_same_component(location, &brain_location)
+            })
+            .min_by_key(|(_, location)| {
+                FloatOrd(location.as_vec2().distance(brain_location.as_vec2()))
+            });
+        if let Some((patient, _)) = patient {
+            claimed.insert(*patient);
+            brain.state = BrainState::Feed(*patient);
+        }
+    }
+}
+
+fn feed_patient(
+    mut commands: Commands,
+    mut brains: Query<(Entity, &AiPath, &mut Brain), Without<PathfindingTask>>,
+    mut pawns: Query<(&Transform, &mut Hunger, &Health)>,
+    structures: Res<Grid<Structure>>,
+    clock: Res<SimClock>,
+) {
+    for (feeder, path, mut brain) in &mut brains {
+        let patient = match brain.state {
+            BrainState::Feed(patient) => patient,
+            _ => continue,
+        };
+        let [(feeder_transform, mut feeder_hunger, _), (patient_transform, mut patient_hunger, patient_health)] =
+            match pawns.get_many_mut([feeder, patient]) {
+                Ok(val) => val,
+                Err(_) => {
+                    // Died before help arrived
+                    brain.state = BrainState::default();
+                    continue;
+                }
+            };
+        if patient_health.value >= INCAPACITATED_HEALTH || feeder_hunger.value <= SPARE_FOOD {
+            brain.state = BrainState::default();
+            continue;
+        }
+
+        let position = feeder_transform.translation.truncate();
+        let patient_position = patient_transform.translation.truncate();
+        if position.distance(patient_position) < 1.0 {
+            if patient_hunger.value < PATIENT_FED {
+                let amount = (FEED_RATE * clock.delta_seconds())
+                    .min(feeder_hunger.value - SPARE_FOOD)
+                    .min(PATIENT_FED - patient_hunger.value);
+                feeder_hunger.value -= amount;
+                patient_hunger.value += amount;
+            }
+            continue;
+        }
+
+        if path.locations.is_empty() {
+            match (
+                GridLocation::from_world(position),
+                GridLocation::from_world(patient_position),
+            ) {
+                (Some(start), Some(end)) => spawn_optimized_pathfinding_task(
+                    &mut commands,
+                    &clock,
+                    feeder,
+                    &structures,
+                    start,
+                    end,
+                ),
+                _ => brain.state = BrainState::default(),
+            }
+        }
+    }
+}
+
+fn mental_break(
+    mut brains: Query<(&mut Brain, &mut Mood, &Transform)>,
+    structures: Res<Grid<Structure>>,
+    mut damage: EventWriter<DamageEvent>,
+    clock: Res<SimClock>,
+) {
+    for (mut brain, mut mood, transform) in &mut brains {
+        let (remaining, tantrum) = match &mut brain.state {
+            BrainState::Sulk(remaining) => (remaining, false),
+            BrainState::Tantrum(remaining) => (remaining, true),
+            _ => continue,
+        };
+        *remaining -= clock.delta_seconds();
+        let finished = *remaining <= 0.0;
+
+        if let (true, Some(location)) = (
+            tantrum,
+            GridLocation::from_world(transform.translation.truncate()),
+        ) {
+            for target in location
+                .neighbors()
+                .iter()
+                .filter_map(|cell| structures[cell])
+            {
+                damage.send(DamageEvent {
+                    target,
+                    amount: TANTRUM_DAMAGE * clock.delta_seconds(),
+                });
+            }
+        }
+        if finished {
+            brain.state = BrainState::default();
+            mood.think(Thought::Catharsis);
+        }
+    }
+}
@@ -4,7 +4,7 @@ pub struct FrameAnimationPlugin;
 
 impl Plugin for FrameAnimationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_character_sprite_animation);
+        app.add_systems(SimulationTick, update_character_sprite_animation);
     }
 }
 
@@ -17,10 +17,10 @@ pub struct AnimationTimer(pub Timer);
 
 fn update_character_sprite_animation(
     mut sprites: Query<(&mut CharacterSprite, &LastDirection, &mut AnimationTimer)>,
-    time: Res<Time>,
+    clock: Res<SimClock>,
 ) {
     for (mut sprite, direction, mut animation) in &mut sprites {
-        animation.tick(time.delta());
+        animation.tick(clock.delta());
         if animation.just_finished() {
             sprite.facing = Facing::from_direction(direction);
             sprite.next_frame();
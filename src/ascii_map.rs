@@ -0,0 +1,277 @@
+use std::{fmt, fs};
+
+use crate::prelude::*;
+
+pub struct AsciiMapPlugin;
+
+impl Plugin for AsciiMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, dump_map);
+    }
+}
+
+// Plain text levels, the last line is y = 0 and each character is one cell:
+//   `.` floor, `#` wall, `P` pawn spawn
+//   `F` food machine with its use tile below, `^` `>` `<` `v` a food machine with its use tile that way
+// Lines are trimmed so fixtures can be indented, short lines are padded with floor
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct AsciiMap {
+    pub walls: Vec<GridLocation>,
+    pub machines: Vec<(GridLocation, Rotation)>,
+    pub spawns: Vec<GridLocation>,
+}
+
+#[derive(Debug)]
+pub enum AsciiMapError {
+    UnknownTile {
+        line: usize,
+        column: usize,
+        tile: char,
+    },
+    TooLarge {
+        width: usize,
+        height: usize,
+    },
+}
+
+impl fmt::Display for AsciiMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsciiMapError::UnknownTile { line, column, tile } => {
+                write!(
+                    f,
+                    "unknown tile {:?} at line {} column {}",
+                    tile, line, column
+                )
+            }
+            AsciiMapError::TooLarge { width, height } => write!(
+                f,
+                "map is {}x{} but the grid is only {size}x{size}",
+                width,
+                height,
+                size = grid_size()
+            ),
+        }
+    }
+}
+
+const MACHINE_GLYPHS: [(char, IVec2); 4] = [
+    ('^', IVec2::Y),
+    ('>', IVec2::X),
+    ('v', IVec2::NEG_Y),
+    ('<', IVec2::NEG_X),
+];
+
+// The rotation that puts a food machine's use tile in this direction
+fn rotation_facing(direction: IVec2) -> Rotation {
+    let definition = BuildingDefinition::food_machine();
+    [
+        Rotation::North,
+        Rotation::East,
+        Rotation::South,
+        Rotation::West,
+    ]
+    .into_iter()
+    .find(|rotation| definition.use_offsets(*rotation)[0] == direction)
+    .unwrap_or_default()
+}
+
+fn machine_glyph(rotation: Rotation) -> char {
+    let direction = BuildingDefinition::food_machine().use_offsets(rotation)[0];
+    match MACHINE_GLYPHS
+        .iter()
+        .find(|(_, offset)| *offset == direction)
+    {
+        Some((glyph, _)) if *glyph != 'v' => *glyph,
+        _ => 'F',
+    }
+}
+
+impl AsciiMap {
+    pub fn parse(text: &str) -> Result<Self, AsciiMapError> {
+        let lines = text
+            .trim_matches('\n')
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let lines = match (
+            lines.iter().position(|line| !line.is_empty()),
+            lines.iter().rposition(|line| !line.is_empty()),
+        ) {
+            (Some(first), Some(last)) => &lines[first..=last],
+            _ => &[],
+        };
+
+        let height = lines.len();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        if width > grid_size() || height > grid_size() {
+            return Err(AsciiMapError::TooLarge { width, height });
+        }
+
+        let mut map = AsciiMap::default();
+        for (row, line) in lines.iter().enumerate() {
+            let y = (height - 1 - row) as u32;
+            for (x, tile) in line.chars().enumerate() {
+                if !map.place(GridLocation::new(x as u32, y), tile) {
+                    return Err(AsciiMapError::UnknownTile {
+                        line: row + 1,
+                        column: x + 1,
+                        tile,
+                    });
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    // Adds whatever the glyph stands for, false if it isn't a known tile
+    pub fn place(&mut self, location: GridLocation, glyph: char) -> bool {
+        match glyph {
+            '.' => {}
+            '#' => self.walls.push(location),
+            'P' => self.spawns.push(location),
+            'F' => self.machines.push((location, Rotation::North)),
+            _ => match MACHINE_GLYPHS.iter().find(|(tile, _)| *tile == glyph) {
+                Some((_, direction)) => self.machines.push((location, rotation_facing(*direction))),
+                None => return false,
+            },
+        }
+        true
+    }
+
+    pub fn to_text(&self) -> String {
+        let cells = self
+            .walls
+            .iter()
+            .map(|location| (location, '#'))
+            .chain(self.spawns.iter().map(|location| (location, 'P')))
+            .chain(
+                self.machines
+                    .iter()
+                    .map(|(location, rotation)| (location, machine_glyph(*rotation))),
+            )
+            .collect::<Vec<_>>();
+        let size = cells
+            .iter()
+            .fold(IVec2::ZERO, |size, (location, _)| size.max(location.0 + 1));
+
+        let mut rows = vec![vec!['.'; size.x as usize]; size.y as usize];
+        for (location, glyph) in cells {
+            rows[(size.y - 1 - location.y) as usize][location.x as usize] = glyph;
+        }
+        rows.into_iter()
+            .map(|row| {
+                // Trailing floor is implied, but every row keeps a cell so y stays right
+                let row = row.into_iter().collect::<String>();
+                let trimmed = row.trim_end_matches('.');
+                if trimmed.is_empty() { "." } else { trimmed }.to_string() + "\n"
+            })
+            .collect()
+    }
+
+    // Walls and machines as a bare occupancy grid, for tests that don't need a world
+    pub fn grid<T>(&self) -> Grid<T> {
+        let mut grid = Grid::default();
+        for location in self
+            .walls
+            .iter()
+            .chain(self.machines.iter().map(|(location, _)| location))
+        {
+            grid[location] = Some(Entity::PLACEHOLDER);
+        }
+        grid
+    }
+
+    // Pawns are left to the scenario, spawns are where they arrive
+    pub fn spawn(&self, commands: &mut Commands) {
+        for location in &self.walls {
+            spawn_wall(commands, location.clone());
+        }
+        for (location, rotation) in &self.machines {
+            Blueprint::FoodMachine {
+                definition: BuildingDefinition::food_machine(),
+                location: location.clone(),
+                rotation: *rotation,
+                rate: 10.0,
+            }
+            .spawn(commands);
+        }
+    }
+}
+
+const DUMP_PATH: &str = "maps/dump.txt";
+
+// F11 writes the current walls, single cell food machines and pawns out as a text map
+fn dump_map(
+    keyboard: Res<Input<KeyCode>>,
+    walls: Res<Grid<Wall>>,
+    machines: Query<(&GridLocation, &Rotation, &Footprint), With<FoodMachine>>,
+    pawns: Query<&Transform, With<Pawn>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F11) {
+        return;
+    }
+
+    let mut map = AsciiMap {
+        walls: walls.iter().map(|(_, location)| location).collect(),
+        ..default()
+    };
+    for (location, rotation, footprint) in &machines {
+        if footprint.0.len() == 1 {
+            map.machines.push((location.clone(), *rotation));
+        } else {
+            warn!("Skipping multi cell machine at {:?}", location);
+        }
+    }
+    map.spawns = pawns
+        .iter()
+        .filter_map(|transform| GridLocation::from_world(transform.translation.truncate()))
+        .collect();
+    // Walls come out of the chunks in no particular order
+    map.walls.sort_by_key(|location| (location.x, location.y));
+
+    let result = fs::create_dir_all("maps").and_then(|_| fs::write(DUMP_PATH, map.to_text()));
+    match result {
+        Ok(()) => info!("Dumped map to {}", DUMP_PATH),
+        Err(err) => warn!("Failed to dump map: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_dump_round_trip() {
+        let text = "
+            .#..
+            .#>.
+            P#.F
+        ";
+        let map = AsciiMap::parse(text).unwrap();
+        assert_eq!(map.walls.len(), 3);
+        assert_eq!(map.spawns, vec![GridLocation::new(0, 0)]);
+        assert!(map
+            .machines
+            .contains(&(GridLocation::new(3, 0), Rotation::North)));
+
+        let (location, rotation) = map.machines[0].clone();
+        let definition = BuildingDefinition::food_machine();
+        assert_eq!(location, GridLocation::new(2, 1));
+        assert_eq!(definition.use_offsets(rotation)[0], IVec2::X);
+
+        assert_eq!(AsciiMap::parse(&map.to_text()).unwrap(), map);
+        assert!(matches!(
+            AsciiMap::parse("..\n.x"),
+            Err(AsciiMapError::UnknownTile {
+                line: 2,
+                column: 2,
+                tile: 'x'
+            })
+        ));
+    }
+}
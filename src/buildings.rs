@@ -1,28 +1,561 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
 
 pub struct BuildingPlugin;
 
 impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
-            GridPlugin::<Wall>::default(),
-            GridPlugin::<Machine>::default(),
-        ));
+        app.init_resource::<Stockpile>()
+            .add_plugins((
+                GridPlugin::<Structure>::default(),
+                GridPlugin::<Wall>::occupancy_only(),
+                GridPlugin::<Machine>::occupancy_only(),
+                GridPlugin::<Floor>::occupancy_only(),
+                GridPlugin::<Furniture>::occupancy_only(),
+                GridPlugin::<Item>::occupancy_only(),
+                GridPlugin::<Wire>::occupancy_only(),
+            ))
+            .add_plugins((
+                ChunkStreamingPlugin::<Grid<Structure>>::new(
+                    chunk_directory("structure"),
+                    STREAM_RADIUS,
+                ),
+                ChunkStreamingPlugin::<Grid<Floor>>::new(chunk_directory("floor"), STREAM_RADIUS),
+                ChunkStreamingPlugin::<Grid<Furniture>>::new(
+                    chunk_directory("furniture"),
+                    STREAM_RADIUS,
+                ),
+                ChunkStreamingPlugin::<Grid<Item>>::new(chunk_directory("item"), STREAM_RADIUS),
+                ChunkStreamingPlugin::<Grid<Wire>>::new(chunk_directory("wire"), STREAM_RADIUS),
+            ));
+    }
+}
+
+// Chunks further than this from every pawn are written to disk, the default map never gets there
+const STREAM_RADIUS: i32 = 8;
+
+// Chunk files only live as long as the run, so each process gets its own directory
+fn chunk_directory(layer: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("colony-chunks-{}-{}", std::process::id(), layer))
+}
+
+// Every cell holds at most one entity per layer
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TileLayer {
+    Floor,
+    Structure,
+    Furniture,
+    Item,
+    Wire,
+}
+
+impl TileLayer {
+    // Layers that can't share a cell with this one, on top of the one entity per layer rule
+    pub fn excludes(&self) -> &'static [TileLayer] {
+        match self {
+            TileLayer::Structure => &[TileLayer::Furniture, TileLayer::Item],
+            TileLayer::Furniture | TileLayer::Item => &[TileLayer::Structure],
+            TileLayer::Floor | TileLayer::Wire => &[],
+        }
     }
 }
 
+// Something that blocks movement, pathfinding and connected components only look at this layer
+#[derive(Component, Default, Debug)]
+pub struct Structure;
+
 // Is default really required
 #[derive(Component, Default, Debug)]
-pub struct Wall {
-    pub _health: f32,
+pub struct Wall;
+
+pub const WALL_COST: u32 = 1;
+
+// Colony wide building materials
+#[derive(Resource, Debug)]
+pub struct Stockpile {
+    pub materials: u32,
+}
+
+impl Default for Stockpile {
+    fn default() -> Self {
+        Self { materials: 100 }
+    }
+}
+
+// What was paid to build something, part of it comes back when deconstructed
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BuildCost(pub u32);
+
+impl BuildCost {
+    pub fn refund(&self) -> u32 {
+        self.0 / 2
+    }
+}
+
+// Designated for deconstruction, a pawn will take it down once progress reaches the work needed
+#[derive(Component, Default, Debug)]
+pub struct Deconstruct {
+    pub progress: f32,
+}
+
+impl Deconstruct {
+    pub const WORK: f32 = 3.0;
+}
+
+pub fn spawn_wall(commands: &mut Commands, location: GridLocation) -> Entity {
+    commands
+        .spawn((
+            SpatialBundle::default(),
+            Structure,
+            Wall,
+            Durability::new(10.0),
+            BuildCost(WALL_COST),
+            LockToGrid,
+            WallSprite::None,
+            location,
+        ))
+        .id()
 }
 
+// Use offsets are relative to the machine's GridLocation and already rotated
 #[derive(Component, Default, Debug)]
 pub struct Machine {
-    pub use_offset: IVec2,
+    pub use_offsets: Vec<IVec2>,
+}
+
+impl Machine {
+    pub fn use_locations<'a>(
+        &'a self,
+        location: &'a GridLocation,
+    ) -> impl Iterator<Item = GridLocation> + 'a {
+        self.use_offsets
+            .iter()
+            .map(|offset| GridLocation(location.0 + *offset))
+    }
+}
+
+// Quarter turns clockwise, buildings are defined facing North
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Rotation {
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Rotation {
+    pub fn next(&self) -> Self {
+        match self {
+            Rotation::North => Rotation::East,
+            Rotation::East => Rotation::South,
+            Rotation::South => Rotation::West,
+            Rotation::West => Rotation::North,
+        }
+    }
+
+    pub fn rotate(&self, offset: IVec2) -> IVec2 {
+        match self {
+            Rotation::North => offset,
+            Rotation::East => IVec2::new(offset.y, -offset.x),
+            Rotation::South => -offset,
+            Rotation::West => IVec2::new(-offset.y, offset.x),
+        }
+    }
+
+    pub fn angle(&self) -> f32 {
+        let quarter_turns = match self {
+            Rotation::North => 0.0,
+            Rotation::East => 1.0,
+            Rotation::South => 2.0,
+            Rotation::West => 3.0,
+        };
+        -quarter_turns * std::f32::consts::FRAC_PI_2
+    }
+}
+
+// Everything needed to place a machine, offsets are for the North rotation
+#[derive(Clone, Debug)]
+pub struct BuildingDefinition {
+    pub footprint: Vec<IVec2>,
+    pub use_offsets: Vec<IVec2>,
+    pub sprite: MachineSprite,
+    pub cost: u32,
+    // Pawns cook their own meals here instead of taking ready food, see FoodMachine
+    pub cooks: bool,
+}
+
+impl BuildingDefinition {
+    pub fn food_machine() -> Self {
+        Self {
+            footprint: vec![IVec2::ZERO],
+            use_offsets: vec![IVec2::new(0, -1)],
+            sprite: MachineSprite::FoodMachine,
+            cost: 5,
+            cooks: false,
+        }
+    }
+
+    pub fn kitchen() -> Self {
+        Self {
+            footprint: vec![IVec2::ZERO, IVec2::X],
+            use_offsets: vec![IVec2::new(0, -1), IVec2::new(1, -1)],
+            sprite: MachineSprite::FoodMachine,
+            cost: 10,
+            cooks: true,
+        }
+    }
+
+    // Every bed houses one pawn, see PopulationPlugin
+    pub fn bed() -> Self {
+        Self {
+            footprint: vec![IVec2::ZERO],
+            use_offsets: Vec::new(),
+            sprite: MachineSprite::Bed,
+            cost: 5,
+            cooks: false,
+        }
+    }
+
+    pub fn footprint(&self, rotation: Rotation) -> Vec<IVec2> {
+        self.footprint
+            .iter()
+            .map(|offset| rotation.rotate(*offset))
+            .collect()
+    }
+
+    pub fn use_offsets(&self, rotation: Rotation) -> Vec<IVec2> {
+        self.use_offsets
+            .iter()
+            .map(|offset| rotation.rotate(*offset))
+            .collect()
+    }
+
+    // Every covered cell must be free and every use tile must stay walkable
+    pub fn can_place(
+        &self,
+        layers: &TileLayers,
+        location: &GridLocation,
+        rotation: Rotation,
+    ) -> bool {
+        let footprint = self.footprint(rotation);
+        let covered = footprint.iter().all(|offset| {
+            layers.can_place(TileLayer::Structure, &GridLocation(location.0 + *offset))
+        });
+        let usable = self.use_offsets(rotation).iter().all(|offset| {
+            !footprint.contains(offset) && layers.passable(&GridLocation(location.0 + *offset))
+        });
+        covered && usable
+    }
+
+    // Spawns the machine with one sprite per covered cell, callers add the machine type
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        location: GridLocation,
+        rotation: Rotation,
+    ) -> Entity {
+        commands
+            .spawn((
+                SpatialBundle::default(),
+                Machine {
+                    use_offsets: self.use_offsets(rotation),
+                },
+                Footprint(self.footprint(rotation)),
+                rotation,
+                Structure,
+                Durability::new(10.0),
+                BuildCost(self.cost),
+                LockToGrid,
+                location,
+            ))
+            .with_children(|parent| {
+                for offset in self.footprint(rotation) {
+                    parent.spawn((
+                        SpatialBundle::from_transform(
+                            Transform::from_translation(offset.as_vec2().extend(0.0))
+                                .with_rotation(Quat::from_rotation_z(rotation.angle())),
+                        ),
+                        self.sprite,
+                    ));
+                }
+            })
+            .id()
+    }
 }
 
 #[derive(Component, Default, Debug)]
 pub struct FoodMachine {
     pub rate: f32,
+    // Meals are cooked by whoever eats them, so their cooking skill trains and sets the quality
+    pub cooked: bool,
+}
+
+#[derive(Component, Default, Debug)]
+pub struct Bed;
+
+#[derive(Component, Default, Debug)]
+pub struct Floor;
+
+#[derive(Component, Default, Debug)]
+pub struct Furniture;
+
+#[derive(Component, Default, Debug)]
+pub struct Item;
+
+#[derive(Component, Default, Debug)]
+pub struct Wire;
+
+#[derive(SystemParam)]
+pub struct TileLayers<'w> {
+    pub floor: Res<'w, Grid<Floor>>,
+    pub structure: Res<'w, Grid<Structure>>,
+    pub furniture: Res<'w, Grid<Furniture>>,
+    pub item: Res<'w, Grid<Item>>,
+    pub wire: Res<'w, Grid<Wire>>,
+}
+
+impl<'w> TileLayers<'w> {
+    pub fn get(&self, layer: TileLayer, location: &GridLocation) -> Option<Entity> {
+        if !location.in_bounds() {
+            return None;
+        }
+        match layer {
+            TileLayer::Floor => self.floor[location],
+            TileLayer::Structure => self.structure[location],
+            TileLayer::Furniture => self.furniture[location],
+            TileLayer::Item => self.item[location],
+            TileLayer::Wire => self.wire[location],
+        }
+    }
+
+    pub fn passable(&self, location: &GridLocation) -> bool {
+        location.in_bounds() && !self.structure.occupied(location)
+    }
+
+    // Nothing is placed in chunks written out to disk, their cells read as empty
+    pub fn can_place(&self, layer: TileLayer, location: &GridLocation) -> bool {
+        location.in_bounds()
+            && !self.structure.entities.is_unloaded(&location.chunk())
+            && self.get(layer, location).is_none()
+            && layer
+                .excludes()
+                .iter()
+                .all(|other| self.get(*other, location).is_none())
+    }
+}
+
+// Everything a placement has to be checked against beyond the tile layers
+#[derive(SystemParam)]
+pub struct PlacementCheck<'w, 's> {
+    pub layers: TileLayers<'w>,
+    components: Res<'w, ConnectedComponents<Structure>>,
+    pawns: Query<'w, 's, &'static Transform, With<Pawn>>,
+    machines: Query<
+        'w,
+        's,
+        (
+            &'static Machine,
+            &'static GridLocation,
+            Option<&'static FoodMachine>,
+        ),
+    >,
+}
+
+// Changes whenever a placement check could give a different answer
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PlacementKey {
+    generations: [u64; 3],
+    components: Option<u64>,
+    pawns: Vec<GridLocation>,
+}
+
+impl<'w, 's> PlacementCheck<'w, 's> {
+    pub fn key(&self) -> PlacementKey {
+        PlacementKey {
+            generations: [
+                self.layers.structure.storage().current_generation(),
+                self.layers.furniture.storage().current_generation(),
+                self.layers.item.storage().current_generation(),
+            ],
+            components: self.components.generation(),
+            pawns: self.pawn_cells(),
+        }
+    }
+
+    fn pawn_cells(&self) -> Vec<GridLocation> {
+        self.pawns
+            .iter()
+            .filter_map(|transform| GridLocation::from_world(transform.translation.truncate()))
+            .collect()
+    }
+
+    // Cells must be free, a machine's use tiles walkable, and no existing use tile or pawn can be blocked off
+    pub fn allows(&self, blueprint: &Blueprint) -> bool {
+        blueprint.can_place(&self.layers)
+            && self.allows_cells(&blueprint.cells(), &blueprint.food_tiles())
+    }
+
+    // For blueprints already checked one by one, together they still can't trap anyone
+    pub fn allows_batch(&self, blueprints: &[Blueprint]) -> bool {
+        let cells = blueprints
+            .iter()
+            .flat_map(|blueprint| blueprint.cells())
+            .collect::<Vec<_>>();
+        let food = blueprints
+            .iter()
+            .flat_map(|blueprint| blueprint.food_tiles())
+            .collect::<Vec<_>>();
+        self.allows_cells(&cells, &food)
+    }
+
+    // For batches already checked cell by cell, new_food are use tiles the batch itself adds
+    pub fn allows_cells(&self, cells: &[GridLocation], new_food: &[GridLocation]) -> bool {
+        let mut food = new_food.to_vec();
+        for (machine, location, food_machine) in &self.machines {
+            for use_location in machine.use_locations(location) {
+                if cells.contains(&use_location) {
+                    return false;
+                }
+                if food_machine.is_some() {
+                    food.push(use_location);
+                }
+            }
+        }
+
+        let pawns = self.pawn_cells();
+        !would_trap(
+            cells,
+            &pawns,
+            &food,
+            |location| self.layers.passable(location),
+            |start, end| self.components.in_same_component(start, end),
+        )
+    }
+}
+
+// Whether blocking cells would land on a pawn or cut one off from every food use tile it can reach now
+pub fn would_trap(
+    blocked: &[GridLocation],
+    pawns: &[GridLocation],
+    food: &[GridLocation],
+    passable: impl Fn(&GridLocation) -> bool,
+    reachable: impl Fn(&GridLocation, &GridLocation) -> bool,
+) -> bool {
+    // Every flood so far found food, so reaching one of its cells reaches food too
+    let mut reaches_food = HashSet::new();
+    for pawn in pawns {
+        if blocked.contains(pawn) {
+            return true;
+        }
+        if reaches_food.contains(pawn) {
+            continue;
+        }
+        let targets = food
+            .iter()
+            .filter(|tile| !blocked.contains(tile) && reachable(pawn, tile))
+            .collect::<HashSet<_>>();
+        if targets.is_empty() {
+            // Already cut off, nothing to lose
+            continue;
+        }
+
+        let mut queue = VecDeque::from([pawn.clone()]);
+        let mut visited = HashSet::from([pawn.clone()]);
+        let mut found = false;
+        while let Some(cell) = queue.pop_front() {
+            if targets.contains(&cell) || reaches_food.contains(&cell) {
+                found = true;
+                break;
+            }
+            for neighbor in cell.neighbors() {
+                if !blocked.contains(&neighbor)
+                    && passable(&neighbor)
+                    && visited.insert(neighbor.clone())
+                {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        if !found {
+            return true;
+        }
+        reaches_food.extend(visited);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+
+    #[test]
+    fn layers_share_a_tile_but_structures_do_not() {
+        let mut world = World::new();
+        world.init_resource::<Grid<Floor>>();
+        world.init_resource::<Grid<Structure>>();
+        world.init_resource::<Grid<Furniture>>();
+        world.init_resource::<Grid<Item>>();
+        world.init_resource::<Grid<Wire>>();
+        let shared = GridLocation::new(2, 2);
+        let walled = GridLocation::new(4, 2);
+        world.resource_mut::<Grid<Floor>>()[&shared] = Some(Entity::from_raw(1));
+        world.resource_mut::<Grid<Furniture>>()[&shared] = Some(Entity::from_raw(2));
+        world.resource_mut::<Grid<Wire>>()[&shared] = Some(Entity::from_raw(3));
+        world.resource_mut::<Grid<Structure>>()[&walled] = Some(Entity::from_raw(4));
+
+        let mut state = SystemState::<TileLayers>::new(&mut world);
+        let layers = state.get(&world);
+        assert!(layers.can_place(TileLayer::Item, &shared));
+        assert!(!layers.can_place(TileLayer::Structure, &shared));
+        assert!(!layers.can_place(TileLayer::Floor, &shared));
+
+        assert!(layers.can_place(TileLayer::Floor, &walled));
+        assert!(layers.can_place(TileLayer::Wire, &walled));
+        assert!(!layers.can_place(TileLayer::Structure, &walled));
+        assert!(!layers.can_place(TileLayer::Item, &walled));
+    }
+
+    #[test]
+    fn trapping_needs_a_lost_food_tile() {
+        let pawns = [GridLocation::new(1, 1)];
+        let food = [GridLocation::new(5, 1)];
+        // An open field where everything is reachable
+        let passable = |_: &GridLocation| true;
+        let reachable = |_: &GridLocation, _: &GridLocation| true;
+
+        assert!(would_trap(&pawns, &pawns, &[], passable, reachable));
+        let wall = [GridLocation::new(3, 1)];
+        assert!(!would_trap(&wall, &pawns, &food, passable, reachable));
+
+        let ring = [
+            GridLocation::new(0, 1),
+            GridLocation::new(2, 1),
+            GridLocation::new(1, 0),
+            GridLocation::new(1, 2),
+        ];
+        assert!(would_trap(&ring, &pawns, &food, passable, reachable));
+        // No food anywhere means there is nothing to be cut off from
+        assert!(!would_trap(&ring, &pawns, &[], passable, reachable));
+    }
+
+    #[test]
+    fn pawns_can_reach_food_through_earlier_floods() {
+        // A corridor where the first pawn's flood covers the second pawn's only way out
+        let corridor = |cell: &GridLocation| cell.y == 1 && (0..=6).contains(&cell.x);
+        let reachable = |_: &GridLocation, _: &GridLocation| true;
+        let pawns = [GridLocation::new(3, 1), GridLocation::new(0, 1)];
+        let food = [GridLocation::new(5, 1)];
+
+        let far_end = [GridLocation::new(6, 1)];
+        assert!(!would_trap(&far_end, &pawns, &food, corridor, reachable));
+        let between = [GridLocation::new(4, 1)];
+        assert!(would_trap(&between, &pawns, &food, corridor, reachable));
+    }
 }
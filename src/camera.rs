@@ -18,8 +18,8 @@ pub struct CameraSettings {
 
 fn spawn_camera(mut commands: Commands) {
     let mut camera = Camera2dBundle::default();
-    camera.transform.translation.x = GRID_SIZE as f32 / 2.0;
-    camera.transform.translation.y = GRID_SIZE as f32 / 2.0;
+    camera.transform.translation.x = grid_size() as f32 / 2.0;
+    camera.transform.translation.y = grid_size() as f32 / 2.0;
 
     camera.projection.scaling_mode = ScalingMode::AutoMin {
         min_width: 64.0,
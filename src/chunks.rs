@@ -0,0 +1,372 @@
+use std::{
+    fs, io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bevy::app::AppExit;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::prelude::*;
+
+pub const CHUNK_SIZE: i32 = 32;
+pub(crate) const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct ChunkLocation(pub IVec2);
+
+impl ChunkLocation {
+    // Every chunk overlapping the map
+    pub fn all() -> impl Iterator<Item = ChunkLocation> {
+        let count = (grid_size() as i32 + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        (0..count).flat_map(move |x| (0..count).map(move |y| ChunkLocation(IVec2::new(x, y))))
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = GridLocation> {
+        let origin = self.0 * CHUNK_SIZE;
+        (0..CHUNK_SIZE).flat_map(move |x| {
+            (0..CHUNK_SIZE).map(move |y| GridLocation(origin + IVec2::new(x, y)))
+        })
+    }
+
+    pub(crate) fn cell(&self, index: usize) -> GridLocation {
+        let index = index as i32;
+        GridLocation(self.0 * CHUNK_SIZE + IVec2::new(index / CHUNK_SIZE, index % CHUNK_SIZE))
+    }
+}
+
+impl GridLocation {
+    pub fn chunk(&self) -> ChunkLocation {
+        ChunkLocation(IVec2::new(
+            self.x.div_euclid(CHUNK_SIZE),
+            self.y.div_euclid(CHUNK_SIZE),
+        ))
+    }
+
+    pub(crate) fn index_in_chunk(&self) -> usize {
+        (self.x.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + self.y.rem_euclid(CHUNK_SIZE)) as usize
+    }
+}
+
+struct Chunk<V> {
+    // Shared so cloning the storage for async tasks is cheap, copied on the first write after
+    values: Arc<Vec<V>>,
+    generation: u64,
+}
+
+impl<V> Clone for Chunk<V> {
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            generation: self.generation,
+        }
+    }
+}
+
+// Lazily allocated chunks, cells in chunks that were never written to read as `empty`
+pub struct ChunkedStorage<V> {
+    chunks: HashMap<ChunkLocation, Chunk<V>>,
+    // Chunks on disk, with the cells written since they were unloaded so loading keeps those
+    unloaded: HashMap<ChunkLocation, Vec<bool>>,
+    empty: V,
+    generation: u64,
+}
+
+impl<V: Clone> Clone for ChunkedStorage<V> {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            unloaded: self.unloaded.clone(),
+            empty: self.empty.clone(),
+            generation: self.generation,
+        }
+    }
+}
+
+impl<V: Clone> ChunkedStorage<V> {
+    pub fn new(empty: V) -> Self {
+        Self {
+            chunks: HashMap::default(),
+            unloaded: HashMap::default(),
+            empty,
+            generation: 0,
+        }
+    }
+
+    pub fn get(&self, location: &GridLocation) -> &V {
+        match self.chunks.get(&location.chunk()) {
+            Some(chunk) => &chunk.values[location.index_in_chunk()],
+            None => &self.empty,
+        }
+    }
+
+    // Allocates the chunk if needed and marks it dirty
+    pub fn get_mut(&mut self, location: &GridLocation) -> &mut V {
+        assert!(location.in_bounds(), "{:?} is not in the grid", location);
+        let index = location.index_in_chunk();
+        if let Some(written) = self.unloaded.get_mut(&location.chunk()) {
+            written[index] = true;
+        }
+        let chunk = self.allocate(location.chunk());
+        &mut Arc::make_mut(&mut chunk.values)[index]
+    }
+
+    fn allocate(&mut self, chunk: ChunkLocation) -> &mut Chunk<V> {
+        self.generation += 1;
+        let generation = self.generation;
+        let empty = &self.empty;
+        let chunk = self.chunks.entry(chunk).or_insert_with(|| Chunk {
+            values: Arc::new(vec![empty.clone(); CHUNK_AREA]),
+            generation,
+        });
+        chunk.generation = generation;
+        chunk
+    }
+
+    // Changes every time a cell in the chunk might have been written, 0 if never allocated
+    pub fn generation(&self, chunk: &ChunkLocation) -> u64 {
+        self.chunks.get(chunk).map_or(0, |chunk| chunk.generation)
+    }
+
+    // The latest generation handed to any chunk
+    pub fn current_generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Chunks written or loaded after `generation`
+    pub fn changed_since(&self, generation: u64) -> impl Iterator<Item = ChunkLocation> + '_ {
+        self.chunks
+            .iter()
+            .filter(move |(_, chunk)| chunk.generation > generation)
+            .map(|(location, _)| *location)
+    }
+
+    pub fn is_unloaded(&self, chunk: &ChunkLocation) -> bool {
+        self.unloaded.contains_key(chunk)
+    }
+
+    pub fn unloaded(&self) -> impl Iterator<Item = &ChunkLocation> + '_ {
+        self.unloaded.keys()
+    }
+
+    // Chunks in memory that aren't waiting to be loaded
+    pub fn loaded(&self) -> impl Iterator<Item = &ChunkLocation> + '_ {
+        self.chunks
+            .keys()
+            .filter(|chunk| !self.unloaded.contains_key(*chunk))
+    }
+
+    pub fn clear(&mut self, empty: V) {
+        self.chunks.clear();
+        self.unloaded.clear();
+        self.empty = empty;
+    }
+
+    // Only cells in allocated chunks, including ones outside the map
+    pub fn iter(&self) -> impl Iterator<Item = (GridLocation, &V)> + '_ {
+        self.chunks.iter().flat_map(|(chunk_location, chunk)| {
+            chunk
+                .values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (chunk_location.cell(i), value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (GridLocation, &mut V)> + '_ {
+        for (chunk, written) in self.unloaded.iter_mut() {
+            if self.chunks.contains_key(chunk) {
+                written.fill(true);
+            }
+        }
+        self.generation += 1;
+        let generation = self.generation;
+        self.chunks
+            .iter_mut()
+            .flat_map(move |(chunk_location, chunk)| {
+                chunk.generation = generation;
+                Arc::make_mut(&mut chunk.values)
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, value)| (chunk_location.cell(i), value))
+            })
+    }
+
+    pub fn allocate_all(&mut self) {
+        for chunk in ChunkLocation::all() {
+            self.allocate(chunk);
+        }
+    }
+}
+
+impl<V: Clone + Serialize + DeserializeOwned> ChunkedStorage<V> {
+    // Writes the chunk to disk and frees it, reads of it return `empty` until it's loaded again
+    pub fn unload(&mut self, chunk: ChunkLocation, directory: &Path) -> io::Result<()> {
+        if self.is_unloaded(&chunk) {
+            return Ok(());
+        }
+        if let Some(data) = self.chunks.get(&chunk) {
+            let serialized = ron::to_string(data.values.as_ref())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            fs::create_dir_all(directory)?;
+            fs::write(chunk_path(&chunk, directory), serialized)?;
+            self.chunks.remove(&chunk);
+            self.unloaded.insert(chunk, vec![false; CHUNK_AREA]);
+            // Its cells now read as occupied, so anything cached off the grid is stale
+            self.generation += 1;
+        }
+        Ok(())
+    }
+
+    // Cells written while the chunk was on disk win over what was saved
+    pub fn load(&mut self, chunk: ChunkLocation, directory: &Path) -> io::Result<()> {
+        if !self.is_unloaded(&chunk) {
+            return Ok(());
+        }
+        let serialized = fs::read_to_string(chunk_path(&chunk, directory))?;
+        let values: Vec<V> = ron::from_str(&serialized)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if values.len() != CHUNK_AREA {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "chunk file has the wrong number of cells",
+            ));
+        }
+        let written = self.unloaded.remove(&chunk).unwrap_or_default();
+        let loaded = self.allocate(chunk);
+        let current = Arc::make_mut(&mut loaded.values);
+        for (i, value) in values.into_iter().enumerate() {
+            if !written.get(i).copied().unwrap_or(false) {
+                current[i] = value;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn chunk_path(chunk: &ChunkLocation, directory: &Path) -> PathBuf {
+    directory.join(format!("{}_{}.ron", chunk.x, chunk.y))
+}
+
+// Grids whose chunks can be written out to disk
+pub trait ChunkedGrid: Resource {
+    type Value: Clone + Serialize + DeserializeOwned;
+
+    fn storage(&self) -> &ChunkedStorage<Self::Value>;
+    fn storage_mut(&mut self) -> &mut ChunkedStorage<Self::Value>;
+}
+
+// Unloads chunks far from every pawn and loads them back when one comes close. Runs at tick
+// boundaries off simulation state only, so the same run always streams the same chunks
+pub struct ChunkStreamingPlugin<G> {
+    pub directory: PathBuf,
+    pub radius: i32,
+    _marker: PhantomData<G>,
+}
+
+impl<G> ChunkStreamingPlugin<G> {
+    pub fn new(directory: impl Into<PathBuf>, radius: i32) -> Self {
+        Self {
+            directory: directory.into(),
+            radius,
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ChunkStreaming<G> {
+    pub directory: PathBuf,
+    pub radius: i32,
+    _marker: PhantomData<G>,
+}
+
+// Ticks between checks, chunks are far bigger than a pawn walks in this time
+const STREAM_INTERVAL: u64 = TICKS_PER_SECOND as u64;
+
+impl<G: ChunkedGrid> Plugin for ChunkStreamingPlugin<G> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ChunkStreaming::<G> {
+            directory: self.directory.clone(),
+            radius: self.radius,
+            _marker: PhantomData,
+        })
+        .add_systems(TickBoundary, stream_chunks::<G>.after(GridSync))
+        .add_systems(Last, remove_chunk_files::<G>);
+    }
+}
+
+// The files are only a cache of the current run
+fn remove_chunk_files<G: ChunkedGrid>(
+    streaming: Res<ChunkStreaming<G>>,
+    mut exit: EventReader<AppExit>,
+) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+    match fs::remove_dir_all(&streaming.directory) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            warn!(
+                "Failed to remove chunk files {:?}: {}",
+                streaming.directory, err
+            )
+        }
+        _ => {}
+    }
+}
+
+fn stream_chunks<G: ChunkedGrid>(
+    mut grid: ResMut<G>,
+    streaming: Res<ChunkStreaming<G>>,
+    pawns: Query<&Transform, With<Pawn>>,
+    clock: Res<SimClock>,
+    mut next_check: Local<u64>,
+) {
+    if clock.tick() < *next_check {
+        return;
+    }
+    *next_check = clock.tick() + STREAM_INTERVAL;
+    // The spawn point stays loaded so an empty colony still has somewhere to build
+    let centers = pawns
+        .iter()
+        .filter_map(|transform| GridLocation::from_world(transform.translation.truncate()))
+        .chain(std::iter::once(spawn_point()))
+        .map(|location| location.chunk())
+        .collect::<Vec<_>>();
+    let in_range = |chunk: &ChunkLocation| {
+        centers.iter().any(|center| {
+            let delta = (chunk.0 - center.0).abs();
+            delta.x.max(delta.y) <= streaming.radius
+        })
+    };
+
+    let to_unload = grid
+        .storage()
+        .loaded()
+        .filter(|chunk| !in_range(chunk))
+        .copied()
+        .collect::<Vec<_>>();
+    let to_load = grid
+        .storage()
+        .unloaded()
+        .filter(|chunk| in_range(chunk))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if to_unload.is_empty() && to_load.is_empty() {
+        return;
+    }
+
+    let storage = grid.storage_mut();
+    for chunk in to_unload {
+        if let Err(err) = storage.unload(chunk, &streaming.directory) {
+            warn!("Failed to unload chunk {:?}: {}", chunk.0, err);
+        }
+    }
+    for chunk in to_load {
+        if let Err(err) = storage.load(chunk, &streaming.directory) {
+            warn!("Failed to load chunk {:?}: {}", chunk.0, err);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use crate::prelude::*;
+use bevy::ecs::schedule::{ExecutorKind, ScheduleLabel};
+use bevy::time::TimeSystem;
+
+pub struct SimClockPlugin;
+
+impl Plugin for SimClockPlugin {
+    fn build(&self, app: &mut App) {
+        // Single threaded so conflicting systems always run in the same order
+        app.edit_schedule(SimulationTick, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        })
+        .edit_schedule(TickBoundary, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        })
+        .edit_schedule(SyncGrids, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        })
+        .init_resource::<SimClock>()
+        .add_systems(First, advance_clock.after(TimeSystem))
+        .add_systems(TickBoundary, sync_grids.in_set(GridSync))
+        .add_systems(Update, (control_clock, run_simulation_ticks));
+    }
+}
+
+// Everything that changes colony state runs here, once per fixed tick
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationTick;
+
+// Runs before every SimulationTick. Player actions, grid sync and async results like paths are
+// applied here so they land on the same tick no matter the frame rate or the thread pool
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TickBoundary;
+
+pub const TICKS_PER_SECOND: u32 = 60;
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND as u64);
+// Async work started on a tick is applied this many ticks later
+const ASYNC_TICKS: u64 = 4;
+// Past this the simulation slows down rather than freezing the frame catching up
+const MAX_TICKS_PER_FRAME: u32 = 16;
+
+pub const SPEEDS: [f32; 3] = [1.0, 2.0, 4.0];
+
+// Simulation time, scaled and pausable, advanced in fixed ticks. Simulation systems read this
+// instead of Time, camera and UI keep using real Time so they stay responsive while paused
+#[derive(Resource)]
+pub struct SimClock {
+    pub paused: bool,
+    pub speed: f32,
+    step_requested: bool,
+    // Scaled real time not yet simulated
+    accumulated: Duration,
+    pending_ticks: u32,
+    tick: u64,
+    // Tick the current run started on
+    run_start: u64,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            step_requested: false,
+            accumulated: Duration::ZERO,
+            pending_ticks: 0,
+            tick: 0,
+            run_start: 0,
+        }
+    }
+}
+
+impl SimClock {
+    // Always one tick, so every run integrates with the same step
+    pub fn delta(&self) -> Duration {
+        TICK
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        1.0 / TICKS_PER_SECOND as f32
+    }
+
+    // Ticks simulated so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    // Simulated seconds since the run started
+    pub fn elapsed_seconds(&self) -> f32 {
+        (self.tick - self.run_start) as f32 / TICKS_PER_SECOND as f32
+    }
+
+    // Starts a new run, unpaused and timed from zero. Ticks keep counting so work already due and
+    // recorded actions stay in order
+    pub fn restart(&mut self) {
+        self.run_start = self.tick;
+        self.paused = false;
+        self.step_requested = false;
+        self.accumulated = Duration::ZERO;
+        self.pending_ticks = 0;
+    }
+
+    // Tick whose boundary applies async work started now. Only blocks if the work still
+    // isn't done by then, so results never depend on how fast the thread pool was
+    pub fn deadline(&self) -> u64 {
+        self.tick + ASYNC_TICKS
+    }
+
+    // Ticks that will run this frame
+    pub fn pending_ticks(&self) -> u32 {
+        self.pending_ticks
+    }
+
+    // Runs a single tick on the next update, only meaningful while paused
+    pub fn step(&mut self) {
+        self.step_requested = true;
+    }
+
+    fn advance(&mut self, real_delta: Duration) {
+        if self.paused {
+            self.accumulated = Duration::ZERO;
+            self.pending_ticks = u32::from(self.step_requested);
+        } else {
+            self.accumulated += real_delta.mul_f32(self.speed);
+            let ticks = (self.accumulated.as_nanos() / TICK.as_nanos()) as u32;
+            self.accumulated -= TICK * ticks;
+            self.pending_ticks = ticks.min(MAX_TICKS_PER_FRAME);
+        }
+        self.step_requested = false;
+    }
+}
+
+fn advance_clock(mut clock: ResMut<SimClock>, time: Res<Time>) {
+    clock.advance(time.delta());
+}
+
+fn run_simulation_ticks(world: &mut World) {
+    let ticks = std::mem::take(&mut world.resource_mut::<SimClock>().pending_ticks);
+    for _ in 0..ticks {
+        world.run_schedule(TickBoundary);
+        world.run_schedule(SimulationTick);
+        world.resource_mut::<SimClock>().tick += 1;
+    }
+}
+
+// Space pauses, F6 to F8 pick a speed and period steps a frame while paused
+fn control_clock(keyboard: Res<Input<KeyCode>>, mut clock: ResMut<SimClock>) {
+    if keyboard.just_pressed(KeyCode::Space) {
+        clock.paused = !clock.paused;
+    }
+    for (key, speed) in [KeyCode::F6, KeyCode::F7, KeyCode::F8]
+        .into_iter()
+        .zip(SPEEDS)
+    {
+        if keyboard.just_pressed(key) {
+            clock.speed = speed;
+            clock.paused = false;
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Period) {
+        clock.step();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_clock_only_moves_on_step() {
+        let mut clock = SimClock {
+            speed: 2.0,
+            ..default()
+        };
+        clock.advance(TICK * 13 / 4);
+        assert_eq!(clock.pending_ticks, 6);
+
+        // Leftover time carries into the next frame
+        clock.advance(TICK / 8);
+        assert_eq!(clock.pending_ticks, 0);
+        clock.advance(TICK / 4);
+        assert_eq!(clock.pending_ticks, 1);
+
+        clock.paused = true;
+        clock.advance(TICK * 10);
+        assert_eq!(clock.pending_ticks, 0);
+
+        clock.step();
+        clock.advance(TICK);
+        assert_eq!(clock.pending_ticks, 1);
+        clock.advance(TICK);
+        assert_eq!(clock.pending_ticks, 0);
+
+        clock.tick = 120;
+        clock.restart();
+        assert!(!clock.paused);
+        assert_eq!(clock.elapsed_seconds(), 0.0);
+    }
+}
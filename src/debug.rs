@@ -0,0 +1,194 @@
+use crate::prelude::*;
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlays>().add_systems(
+            Update,
+            (
+                toggle_overlays,
+                draw_paths,
+                draw_components,
+                draw_occupancy::<Structure>.run_if(occupancy_enabled),
+                draw_occupancy::<Wall>.run_if(occupancy_enabled),
+                draw_occupancy::<Machine>.run_if(occupancy_enabled),
+                draw_occupancy::<Floor>.run_if(occupancy_enabled),
+                draw_occupancy::<Furniture>.run_if(occupancy_enabled),
+                draw_occupancy::<Item>.run_if(occupancy_enabled),
+                draw_occupancy::<Wire>.run_if(occupancy_enabled),
+                draw_use_tiles,
+                draw_pathfinding_tasks,
+            ),
+        );
+    }
+}
+
+// Gizmo overlays for diagnosing the AI, F1 to F5 toggle them
+#[derive(Resource, Default)]
+pub struct DebugOverlays {
+    pub paths: bool,
+    pub components: bool,
+    pub occupancy: bool,
+    pub use_tiles: bool,
+    pub tasks: bool,
+}
+
+// Each grid gets its own color and inset so overlapping layers stay readable
+trait OverlayStyle {
+    const COLOR: Color;
+    const SIZE: f32;
+}
+
+impl OverlayStyle for Structure {
+    const COLOR: Color = Color::RED;
+    const SIZE: f32 = 0.95;
+}
+
+impl OverlayStyle for Wall {
+    const COLOR: Color = Color::ORANGE;
+    const SIZE: f32 = 0.85;
+}
+
+impl OverlayStyle for Machine {
+    const COLOR: Color = Color::YELLOW;
+    const SIZE: f32 = 0.75;
+}
+
+impl OverlayStyle for Floor {
+    const COLOR: Color = Color::GRAY;
+    const SIZE: f32 = 0.65;
+}
+
+impl OverlayStyle for Furniture {
+    const COLOR: Color = Color::PURPLE;
+    const SIZE: f32 = 0.55;
+}
+
+impl OverlayStyle for Item {
+    const COLOR: Color = Color::CYAN;
+    const SIZE: f32 = 0.45;
+}
+
+impl OverlayStyle for Wire {
+    const COLOR: Color = Color::LIME_GREEN;
+    const SIZE: f32 = 0.35;
+}
+
+fn toggle_overlays(keyboard: Res<Input<KeyCode>>, mut overlays: ResMut<DebugOverlays>) {
+    if keyboard.just_pressed(KeyCode::F1) {
+        overlays.paths = !overlays.paths;
+    }
+    if keyboard.just_pressed(KeyCode::F2) {
+        overlays.components = !overlays.components;
+    }
+    if keyboard.just_pressed(KeyCode::F3) {
+        overlays.occupancy = !overlays.occupancy;
+    }
+    if keyboard.just_pressed(KeyCode::F4) {
+        overlays.use_tiles = !overlays.use_tiles;
+    }
+    if keyboard.just_pressed(KeyCode::F5) {
+        overlays.tasks = !overlays.tasks;
+    }
+}
+
+fn occupancy_enabled(overlays: Res<DebugOverlays>) -> bool {
+    overlays.occupancy
+}
+
+// World space rectangle the camera can see, overlays skip anything outside it
+fn visible_area(camera: &Query<(&Transform, &OrthographicProjection), With<Camera2d>>) -> Rect {
+    let (transform, projection) = camera.single();
+    let center = transform.translation.truncate();
+    Rect::from_corners(
+        center + projection.area.min - Vec2::ONE,
+        center + projection.area.max + Vec2::ONE,
+    )
+}
+
+fn draw_paths(
+    mut gizmos: Gizmos,
+    overlays: Res<DebugOverlays>,
+    paths: Query<(&Transform, &AiPath)>,
+) {
+    if !overlays.paths {
+        return;
+    }
+    for (transform, path) in &paths {
+        if path.locations.is_empty() {
+            continue;
+        }
+        gizmos.linestrip_2d(
+            std::iter::once(transform.translation.truncate()).chain(path.locations.iter().copied()),
+            Color::WHITE,
+        );
+        if let Some(end) = path.locations.back() {
+            gizmos.circle_2d(*end, 0.2, Color::WHITE);
+        }
+    }
+}
+
+fn draw_components(
+    mut gizmos: Gizmos,
+    overlays: Res<DebugOverlays>,
+    components: Res<ConnectedComponents<Structure>>,
+    camera: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    if !overlays.components {
+        return;
+    }
+    let area = visible_area(&camera);
+    let (min, max) = (area.min.as_ivec2(), area.max.as_ivec2());
+    for cell in
+        (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |y| GridLocation(IVec2::new(x, y))))
+    {
+        if let Some(index) = components.component(&cell) {
+            // Golden angle steps keep neighbouring indices far apart in hue
+            let color = Color::hsla((index as f32 * 137.5) % 360.0, 0.8, 0.5, 0.6);
+            gizmos.rect_2d(cell.as_vec2(), 0.0, Vec2::splat(0.3), color);
+        }
+    }
+}
+
+fn draw_occupancy<T: OverlayStyle + Component>(
+    mut gizmos: Gizmos,
+    grid: Res<Grid<T>>,
+    camera: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    let area = visible_area(&camera);
+    for (_, location) in grid.iter() {
+        if area.contains(location.as_vec2()) {
+            gizmos.rect_2d(location.as_vec2(), 0.0, Vec2::splat(T::SIZE), T::COLOR);
+        }
+    }
+}
+
+fn draw_use_tiles(
+    mut gizmos: Gizmos,
+    overlays: Res<DebugOverlays>,
+    machines: Query<(&Machine, &GridLocation)>,
+) {
+    if !overlays.use_tiles {
+        return;
+    }
+    for (machine, location) in &machines {
+        for use_location in machine.use_locations(location) {
+            gizmos.line_2d(location.as_vec2(), use_location.as_vec2(), Color::GREEN);
+            gizmos.circle_2d(use_location.as_vec2(), 0.3, Color::GREEN);
+        }
+    }
+}
+
+fn draw_pathfinding_tasks(
+    mut gizmos: Gizmos,
+    overlays: Res<DebugOverlays>,
+    tasks: Query<&Transform, With<PathfindingTask>>,
+) {
+    if !overlays.tasks {
+        return;
+    }
+    for transform in &tasks {
+        gizmos.circle_2d(transform.translation.truncate(), 0.5, Color::FUCHSIA);
+    }
+}
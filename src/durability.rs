@@ -0,0 +1,230 @@
+use crate::prelude::*;
+
+pub struct DurabilityPlugin;
+
+impl Plugin for DurabilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .init_resource::<Explosions>()
+            .add_systems(
+                SimulationTick,
+                (
+                    apply_hazards,
+                    explode,
+                    apply_damage.after(apply_hazards).after(explode),
+                    destroy_structures.after(apply_damage),
+                ),
+            )
+            .add_systems(Update, (show_wear, draw_hazards, queue_explosion_at_cursor));
+    }
+}
+
+// Health of walls and machines, anything without one (like the map outline) can't be damaged
+#[derive(Component, Debug)]
+pub struct Durability {
+    pub health: f32,
+    pub max: f32,
+}
+
+impl Durability {
+    pub fn new(max: f32) -> Self {
+        Self { health: max, max }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        (self.health / self.max).clamp(0.0, 1.0)
+    }
+
+    pub fn damaged(&self) -> bool {
+        self.health < self.max
+    }
+}
+
+// Hostiles or anything else hurting a specific structure
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+// Damages every structure in the radius, falling off with distance
+#[derive(Clone, Debug)]
+pub struct Explosion {
+    pub location: GridLocation,
+    pub radius: f32,
+    pub damage: f32,
+}
+
+impl Explosion {
+    // What the X key sets off
+    pub fn small(location: GridLocation) -> Self {
+        Self {
+            location,
+            radius: 2.5,
+            damage: 8.0,
+        }
+    }
+}
+
+// Held until the next tick instead of sent as an event, so an explosion queued while paused
+// isn't dropped when the event buffers rotate
+#[derive(Resource, Default)]
+pub struct Explosions {
+    pending: Vec<Explosion>,
+}
+
+impl Explosions {
+    pub fn queue(&mut self, explosion: Explosion) {
+        self.pending.push(explosion);
+    }
+}
+
+// Fire or a logic controlled hazard, damages structures in and next to its cell while active.
+// Scenarios place them, see ScenarioHazard
+#[derive(Component)]
+pub struct Hazard {
+    pub damage_per_second: f32,
+    pub active: bool,
+}
+
+pub fn spawn_hazard(
+    commands: &mut Commands,
+    location: GridLocation,
+    damage_per_second: f32,
+) -> Entity {
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(
+                location.as_vec2().extend(0.0),
+            )),
+            Hazard {
+                damage_per_second,
+                active: true,
+            },
+            location,
+        ))
+        .id()
+}
+
+fn apply_hazards(
+    hazards: Query<(&Hazard, &GridLocation)>,
+    structures: Res<Grid<Structure>>,
+    mut damage: EventWriter<DamageEvent>,
+    clock: Res<SimClock>,
+) {
+    for (hazard, location) in &hazards {
+        if !hazard.active {
+            continue;
+        }
+        let mut cells = location.neighbors();
+        cells.push(location.clone());
+        for cell in cells {
+            if let Some(target) = structures[&cell] {
+                damage.send(DamageEvent {
+                    target,
+                    amount: hazard.damage_per_second * clock.delta_seconds(),
+                });
+            }
+        }
+    }
+}
+
+fn explode(
+    mut explosions: ResMut<Explosions>,
+    structures: Res<Grid<Structure>>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    for explosion in explosions.pending.drain(..) {
+        let center = explosion.location.as_vec2();
+        let mut hit = HashMap::<Entity, f32>::default();
+        for (entity, location) in structures.iter() {
+            let distance = location.as_vec2().distance(center);
+            if distance <= explosion.radius {
+                let amount = explosion.damage * (1.0 - distance / (explosion.radius + 1.0));
+                // Multi tile structures take the hit once, from their closest cell
+                let entry = hit.entry(entity).or_insert(0.0);
+                *entry = entry.max(amount);
+            }
+        }
+        for (target, amount) in hit {
+            damage.send(DamageEvent { target, amount });
+        }
+    }
+}
+
+pub fn apply_damage(
+    mut events: EventReader<DamageEvent>,
+    mut durabilities: Query<&mut Durability>,
+) {
+    for event in events.iter() {
+        if let Ok(mut durability) = durabilities.get_mut(event.target) {
+            durability.health -= event.amount;
+        }
+    }
+}
+
+// Despawning lets the grid plugins clear the cells and update connected components
+fn destroy_structures(
+    mut commands: Commands,
+    durabilities: Query<(Entity, &Durability), Changed<Durability>>,
+) {
+    for (entity, durability) in &durabilities {
+        if durability.health <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Darkens wall sprites and machine part sprites as they lose health, designated ones are tinted red
+fn show_wear(
+    changed: Query<
+        Entity,
+        (
+            With<Durability>,
+            Or<(Changed<Durability>, Changed<Deconstruct>)>,
+        ),
+    >,
+    mut undesignated: RemovedComponents<Deconstruct>,
+    structures: Query<(&Durability, Option<&Deconstruct>, Option<&Children>)>,
+    mut sprites: Query<&mut TextureAtlasSprite>,
+) {
+    for entity in changed.iter().chain(undesignated.iter()) {
+        let (durability, deconstruct, children) = match structures.get(entity) {
+            Ok(val) => val,
+            Err(_) => continue,
+        };
+        let shade = 0.4 + 0.6 * durability.fraction();
+        let color = match deconstruct {
+            Some(_) => Color::rgb(shade, shade * 0.5, shade * 0.5),
+            None => Color::rgb(shade, shade, shade),
+        };
+        // Walls carry their own sprite, machines have one per covered cell
+        let parts = std::iter::once(entity).chain(children.into_iter().flatten().copied());
+        let mut parts = sprites.iter_many_mut(parts);
+        while let Some(mut sprite) = parts.fetch_next() {
+            sprite.color = color;
+        }
+    }
+}
+
+// No sprite yet, a flickering ring marks active hazards
+fn draw_hazards(mut gizmos: Gizmos, hazards: Query<(&Hazard, &GridLocation)>, time: Res<Time>) {
+    let flicker = 0.35 + 0.1 * (time.elapsed_seconds() * 8.0).sin();
+    for (hazard, location) in &hazards {
+        if hazard.active {
+            gizmos.circle_2d(location.as_vec2(), flicker, Color::ORANGE_RED);
+        }
+    }
+}
+
+fn queue_explosion_at_cursor(
+    keyboard: Res<Input<KeyCode>>,
+    cursor_position: Res<CursorPosition>,
+    mut actions: ResMut<PlayerActions>,
+) {
+    if keyboard.just_pressed(KeyCode::X) {
+        if let Some(location) = GridLocation::from_world(cursor_position.world_position) {
+            actions.push(PlayerAction::Explode(location));
+        }
+    }
+}
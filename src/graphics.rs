@@ -127,6 +127,7 @@ impl IndexableSprite for CharacterSprite {
 pub enum MachineSprite {
     #[default]
     FoodMachine,
+    Bed,
 }
 
 impl IndexableSprite for MachineSprite {
@@ -134,6 +135,7 @@ impl IndexableSprite for MachineSprite {
     fn index(&self) -> usize {
         match self {
             MachineSprite::FoodMachine => 14 + 16 * 4,
+            MachineSprite::Bed => 13 + 16 * 4,
         }
     }
 }
@@ -1,197 +1,515 @@
 use std::{
-    collections::HashSet,
+    collections::HashMap,
     marker::PhantomData,
     ops::{Index, IndexMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use bevy::{
+    ecs::{
+        event::ManualEventReader, removal_detection::RemovedComponentEntity,
+        schedule::ScheduleLabel,
+    },
     prelude::*,
     tasks::{AsyncComputeTaskPool, Task},
 };
 use futures_lite::future;
-use pathfinding::undirected::connected_components;
-use rand::{seq::IteratorRandom, Rng};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 
-use crate::prelude::neumann_neighbors;
+use crate::prelude::{
+    neumann_neighbors, ChunkLocation, ChunkedGrid, ChunkedStorage, SimClock, TickBoundary,
+    CHUNK_AREA, CHUNK_SIZE,
+};
+
+pub const DEFAULT_GRID_SIZE: usize = 200;
+// Map generation and the spawn area need at least a chunk of room
+pub const MIN_GRID_SIZE: usize = CHUNK_SIZE as usize;
 
-pub const GRID_SIZE: usize = 200;
+// Cells along each side of the map. Chunks are only allocated once written so the size is
+// bounded by what gets built, set it with `--size` before anything reads it
+static GRID_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_GRID_SIZE);
+
+pub fn grid_size() -> usize {
+    GRID_SIZE.load(Ordering::Relaxed)
+}
+
+pub fn set_grid_size(size: usize) {
+    GRID_SIZE.store(size, Ordering::Relaxed);
+}
 
 #[derive(Resource)]
 pub struct Grid<T> {
-    pub entities: [[Option<Entity>; GRID_SIZE]; GRID_SIZE],
+    pub entities: ChunkedStorage<Option<Entity>>,
     _marker: PhantomData<T>,
 }
 
+// Open cells of one chunk, split into regions connected inside the chunk
+struct ChunkRegions {
+    // Region of each cell, None where it is blocked
+    region_of: Vec<Option<usize>>,
+    sizes: Vec<usize>,
+}
+
 #[derive(Resource)]
 pub struct ConnectedComponents<T> {
-    pub components: Vec<HashSet<GridLocation>>,
+    chunk_regions: HashMap<ChunkLocation, Arc<ChunkRegions>>,
+    // Component of every region in each chunk
+    component_of: HashMap<ChunkLocation, Vec<usize>>,
+    // Cells in each component
+    sizes: Vec<usize>,
+    // Storage generation the regions were computed from, None before the first time
+    generation: Option<u64>,
     _marker: PhantomData<T>,
 }
 
-#[derive(Component, Eq, PartialEq, Hash, Clone, Debug, Deref, DerefMut)]
+#[derive(Component, Eq, PartialEq, Hash, Clone, Debug, Deref, DerefMut, Serialize, Deserialize)]
 pub struct GridLocation(pub IVec2);
 
 #[derive(Component)]
 pub struct LockToGrid;
 
+// Cells covered by a multi tile entity, as offsets from its GridLocation. Entities without one cover a single cell
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct Footprint(pub Vec<IVec2>);
+
+// Where each entity was put in the grid, so it can be cleared or reverted without searching the grid
+#[derive(Resource)]
+struct Placements<T> {
+    placed: HashMap<Entity, Placement>,
+    // Removals seen but not cleared yet. Removal events only last two frames and frames can go
+    // by without a tick, so they are collected every frame and cleared at the next boundary
+    removed: Vec<Entity>,
+    removed_reader: ManualEventReader<RemovedComponentEntity>,
+    _marker: PhantomData<T>,
+}
+
+#[derive(Clone, PartialEq)]
+struct Placement {
+    anchor: GridLocation,
+    footprint: Vec<IVec2>,
+}
+
+// Every cell an entity covers in the grid
+pub fn covered_cells(location: &GridLocation, footprint: Option<&Footprint>) -> Vec<GridLocation> {
+    Placement::new(location, footprint).cells().collect()
+}
+
+impl Placement {
+    fn new(anchor: &GridLocation, footprint: Option<&Footprint>) -> Self {
+        Self {
+            anchor: anchor.clone(),
+            footprint: footprint.map_or_else(|| vec![IVec2::ZERO], |footprint| footprint.0.clone()),
+        }
+    }
+
+    fn cells(&self) -> impl Iterator<Item = GridLocation> + '_ {
+        self.footprint
+            .iter()
+            .map(|offset| GridLocation(self.anchor.0 + *offset))
+    }
+}
+
 #[derive(Event)]
 pub struct DirtyGridEvent<T>(pub GridLocation, PhantomData<T>);
 
-#[derive(Default)]
+// Keeps every grid in step with its entities at each tick boundary
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GridSync;
+
+// The grid plugins' sync systems, its own schedule so applying player actions can also run it
+// between actions. `sync_grids` runs it in the GridSync set
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SyncGrids;
+
+pub fn sync_grids(world: &mut World) {
+    world.run_schedule(SyncGrids);
+}
+
 pub struct GridPlugin<T> {
+    connected_components: bool,
     _marker: PhantomData<T>,
 }
 
+impl<T> Default for GridPlugin<T> {
+    fn default() -> Self {
+        Self {
+            connected_components: true,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> GridPlugin<T> {
+    // For layers nothing paths through, skips the connected components work
+    pub fn occupancy_only() -> Self {
+        Self {
+            connected_components: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
 impl<T: Component> Plugin for GridPlugin<T> {
     fn build(&self, app: &mut App) {
         app.init_resource::<Grid<T>>()
-            .init_resource::<ConnectedComponents<T>>()
-            .add_systems(
-                Update,
-                (lock_to_grid::<T>, update_connected_components::<T>),
-            )
+            .insert_resource(Placements::<T> {
+                placed: HashMap::new(),
+                removed: Vec::new(),
+                removed_reader: default(),
+                _marker: PhantomData,
+            })
             .add_event::<DirtyGridEvent<T>>()
-            // TODO move_on_grid / GridLocation change detection
-            .add_systems(Startup, first_dirty_event::<T>)
             .add_systems(
-                PreUpdate,
+                SyncGrids,
                 (
-                    add_to_grid::<T>,
+                    collect_removed::<T>,
                     remove_from_grid::<T>,
+                    add_to_grid::<T>,
+                    move_on_grid::<T>,
+                    lock_to_grid::<T>,
+                )
+                    .chain(),
+            )
+            .add_systems(Last, collect_removed::<T>);
+
+        if self.connected_components {
+            app.init_resource::<ConnectedComponents<T>>().add_systems(
+                TickBoundary,
+                (
                     resolve_connected_components::<T>,
-                ),
+                    update_connected_components::<T>,
+                )
+                    .chain()
+                    .after(GridSync),
             );
+        }
     }
 }
 
-// Could change detect
 fn lock_to_grid<T: Component>(
-    grid: Res<Grid<T>>,
-    mut positions: Query<&mut Transform, (With<LockToGrid>, With<T>)>,
+    mut positions: Query<
+        (&GridLocation, &mut Transform),
+        (With<LockToGrid>, With<T>, Changed<GridLocation>),
+    >,
 ) {
-    for (entity, location) in grid.iter() {
-        if let Ok(mut position) = positions.get_mut(entity) {
-            position.translation.x = location.x as f32;
-            position.translation.y = location.y as f32;
-        }
+    for (location, mut position) in &mut positions {
+        position.translation.x = location.x as f32;
+        position.translation.y = location.y as f32;
     }
 }
 
-// Forces some sane initializations of connected components
-fn first_dirty_event<T: Component>(mut dirty: EventWriter<DirtyGridEvent<T>>) {
-    dirty.send(DirtyGridEvent::<T>(
-        GridLocation::new(0, 0),
-        PhantomData::default(),
-    ));
-}
-
 #[derive(Component)]
 struct ConnectedTask<T> {
     task: Task<ConnectedComponents<T>>,
+    due: u64,
 }
 
+// Applied on the tick they were due, later requests hold every change an earlier one did
 fn resolve_connected_components<T: Component>(
     mut commands: Commands,
     mut connected: ResMut<ConnectedComponents<T>>,
     // Should maybe be a resource?
     mut tasks: Query<(Entity, &mut ConnectedTask<T>)>,
+    clock: Res<SimClock>,
 ) {
-    for (task_entity, mut task) in &mut tasks {
-        if let Some(result) = future::block_on(future::poll_once(&mut task.task)) {
-            //TODO is there a way to make bevy auto remove these or not panic or something
-            commands.entity(task_entity).despawn_recursive();
-            *connected = result;
-        }
+    let mut due = tasks
+        .iter_mut()
+        .filter(|(_, task)| task.due <= clock.tick())
+        .collect::<Vec<_>>();
+    due.sort_by_key(|(_, task)| task.due);
+    for (task_entity, mut task) in due {
+        //TODO is there a way to make bevy auto remove these or not panic or something
+        commands.entity(task_entity).despawn_recursive();
+        *connected = future::block_on(&mut task.task);
     }
 }
 
+// Recomputes whenever the grid has been written since the last request, the first run computes
+// everything
 fn update_connected_components<T: Component>(
     mut commands: Commands,
     grid: Res<Grid<T>>,
-    mut events: EventReader<DirtyGridEvent<T>>,
-    // Should maybe be a resource?
-    current_tasks: Query<Entity, With<ConnectedTask<T>>>,
+    connected: Res<ConnectedComponents<T>>,
+    mut requested: Local<Option<u64>>,
+    clock: Res<SimClock>,
 ) {
-    if !events.is_empty() {
-        events.clear();
-        for task in &current_tasks {
-            commands.entity(task).despawn_recursive();
-        }
+    let generation = grid.entities.current_generation();
+    if *requested == Some(generation) {
+        return;
+    }
+    *requested = Some(generation);
+
+    let thread_pool = AsyncComputeTaskPool::get();
+    let grid = grid.clone();
+    let cached = connected.chunk_regions.clone();
+    let since = connected.generation;
+
+    let task =
+        thread_pool.spawn(async move { compute_connected_components(&grid, &cached, since) });
+
+    commands.spawn(ConnectedTask {
+        task,
+        due: clock.deadline(),
+    });
+}
 
-        let thread_pool = AsyncComputeTaskPool::get();
-        let grid = Box::new(grid.clone());
+// Regions are only recomputed for chunks written since `since`, then joined across chunk borders.
+// Chunks on disk have no regions, the same as `occupied` treating them as solid
+fn compute_connected_components<T>(
+    grid: &Grid<T>,
+    cached: &HashMap<ChunkLocation, Arc<ChunkRegions>>,
+    since: Option<u64>,
+) -> ConnectedComponents<T> {
+    let mut chunk_regions = cached.clone();
+    let stale = match since {
+        Some(since) => grid.entities.changed_since(since).collect::<Vec<_>>(),
+        None => ChunkLocation::all().collect(),
+    };
+    chunk_regions.retain(|chunk, _| !grid.entities.is_unloaded(chunk));
+    for chunk in stale {
+        if !grid.entities.is_unloaded(&chunk) {
+            chunk_regions.insert(chunk, Arc::new(regions_in_chunk(grid, chunk)));
+        }
+    }
 
-        let task = thread_pool.spawn(async move {
-            let starts = all_points()
-                .into_iter()
-                .filter(|point| !grid.occupied(point))
-                .collect::<Vec<_>>();
+    // Sorted so the same grid always numbers its components the same way
+    let mut chunks = chunk_regions.keys().copied().collect::<Vec<_>>();
+    chunks.sort_unstable_by_key(|chunk| (chunk.x, chunk.y));
+    let mut first_region = HashMap::new();
+    let mut region_count = 0;
+    for chunk in &chunks {
+        first_region.insert(*chunk, region_count);
+        region_count += chunk_regions[chunk].sizes.len();
+    }
 
-            ConnectedComponents::<T> {
-                components: connected_components::connected_components(&starts, |p| {
-                    neumann_neighbors(&grid, p)
-                }),
-                ..default()
+    // Union find over regions touching across chunk borders, only the border cells are looked at
+    let mut parents = (0..region_count).collect::<Vec<_>>();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for chunk in &chunks {
+        let regions = &chunk_regions[chunk];
+        for (step, along) in [(IVec2::X, IVec2::Y), (IVec2::Y, IVec2::X)] {
+            let next = ChunkLocation(chunk.0 + step);
+            let next_regions = match chunk_regions.get(&next) {
+                Some(regions) => regions,
+                None => continue,
+            };
+            let edge = chunk.0 * CHUNK_SIZE + step * (CHUNK_SIZE - 1);
+            for i in 0..CHUNK_SIZE {
+                let cell = GridLocation(edge + along * i);
+                let neighbor = GridLocation(cell.0 + step);
+                if let (Some(a), Some(b)) = (
+                    regions.region_of[cell.index_in_chunk()],
+                    next_regions.region_of[neighbor.index_in_chunk()],
+                ) {
+                    let (a, b) = (
+                        root(&mut parents, first_region[chunk] + a),
+                        root(&mut parents, first_region[&next] + b),
+                    );
+                    parents[a] = b;
+                }
             }
-        });
+        }
+    }
 
-        commands.spawn(ConnectedTask { task });
+    let mut component_ids = HashMap::new();
+    let mut component_of = HashMap::new();
+    let mut sizes = Vec::new();
+    for chunk in &chunks {
+        let regions = &chunk_regions[chunk];
+        let components = regions
+            .sizes
+            .iter()
+            .enumerate()
+            .map(|(region, size)| {
+                let root = root(&mut parents, first_region[chunk] + region);
+                let id = *component_ids.entry(root).or_insert_with(|| {
+                    sizes.push(0);
+                    sizes.len() - 1
+                });
+                sizes[id] += size;
+                id
+            })
+            .collect();
+        component_of.insert(*chunk, components);
+    }
+
+    ConnectedComponents {
+        chunk_regions,
+        component_of,
+        sizes,
+        generation: Some(grid.entities.current_generation()),
+        _marker: PhantomData,
+    }
+}
+
+fn regions_in_chunk<T>(grid: &Grid<T>, chunk: ChunkLocation) -> ChunkRegions {
+    let mut region_of = vec![None; CHUNK_AREA];
+    let mut sizes = Vec::new();
+    for start in chunk.cells() {
+        if region_of[start.index_in_chunk()].is_some()
+            || !start.in_bounds()
+            || grid.occupied(&start)
+        {
+            continue;
+        }
+        let region = sizes.len();
+        region_of[start.index_in_chunk()] = Some(region);
+        let mut size = 0;
+        let mut open = vec![start];
+        while let Some(cell) = open.pop() {
+            size += 1;
+            for neighbor in neumann_neighbors(grid, &cell) {
+                if neighbor.chunk() == chunk && region_of[neighbor.index_in_chunk()].is_none() {
+                    region_of[neighbor.index_in_chunk()] = Some(region);
+                    open.push(neighbor);
+                }
+            }
+        }
+        sizes.push(size);
     }
+    ChunkRegions { region_of, sizes }
+}
+
+// Exclusive so the boundary and Last share one reader and never see a removal twice
+fn collect_removed<T: Component>(world: &mut World) {
+    let component = match world.component_id::<T>() {
+        Some(component) => component,
+        None => return,
+    };
+    world.resource_scope(|world, mut placements: Mut<Placements<T>>| {
+        if let Some(events) = world.removed_components().get(component) {
+            let placements = placements.as_mut();
+            let removed = placements.removed_reader.iter(events).cloned();
+            placements.removed.extend(removed.map(Entity::from));
+        }
+    });
 }
 
 fn remove_from_grid<T: Component>(
     mut grid: ResMut<Grid<T>>,
-    mut query: RemovedComponents<T>,
+    mut placements: ResMut<Placements<T>>,
+    mut dirty: EventWriter<DirtyGridEvent<T>>,
+) {
+    for removed_entity in std::mem::take(&mut placements.removed) {
+        if let Some(placement) = placements.placed.remove(&removed_entity) {
+            clear_cells(&mut grid, &placement, removed_entity, &mut dirty);
+        }
+    }
+}
+
+// Only clears cells still holding the entity, another entity may have over-written it. Cells on
+// disk can't be checked and are always cleared, nothing else can have been placed there
+fn clear_cells<T: Component>(
+    grid: &mut Grid<T>,
+    placement: &Placement,
+    entity: Entity,
+    dirty: &mut EventWriter<DirtyGridEvent<T>>,
+) {
+    for cell in placement.cells() {
+        if !cell.in_bounds() {
+            continue;
+        }
+        if grid.entities.is_unloaded(&cell.chunk()) || grid[&cell] == Some(entity) {
+            grid[&cell] = None;
+            dirty.send(DirtyGridEvent::<T>(cell, PhantomData));
+        }
+    }
+}
+
+// Keeps the grid in sync when an entity's GridLocation or Footprint is changed after spawning
+fn move_on_grid<T: Component>(
+    mut grid: ResMut<Grid<T>>,
+    mut placements: ResMut<Placements<T>>,
+    mut query: Query<
+        (Entity, &mut GridLocation, Option<&mut Footprint>),
+        (Or<(Changed<GridLocation>, Changed<Footprint>)>, With<T>),
+    >,
     mut dirty: EventWriter<DirtyGridEvent<T>>,
 ) {
-    for removed_entity in query.iter() {
-        // Search for entity
-        let removed = grid.iter().find(|(entity, _)| *entity == removed_entity);
-        if let Some((_, location)) = removed {
-            dirty.send(DirtyGridEvent::<T>(
-                location.clone(),
-                PhantomData::default(),
-            ));
-            grid[&location] = None;
+    for (entity, mut location, footprint) in &mut query {
+        let old_placement = match placements.placed.get(&entity) {
+            Some(placement) => placement.clone(),
+            None => continue,
+        };
+        let new_placement = Placement::new(&location, footprint.as_deref());
+        if new_placement == old_placement {
+            // Newly added or already in place
+            continue;
+        }
+
+        let blocked = new_placement.cells().any(|cell| {
+            !Grid::<T>::valid_index(&cell) || grid[&cell].is_some_and(|existing| existing != entity)
+        });
+
+        if blocked {
+            warn!("Can't move entity to {:?}", location.0);
+            // Bypass change detection so the revert doesn't look like another move
+            *location.bypass_change_detection() = old_placement.anchor;
+            if let Some(mut footprint) = footprint {
+                footprint.bypass_change_detection().0 = old_placement.footprint;
+            }
+            continue;
         }
+
+        clear_cells(&mut grid, &old_placement, entity, &mut dirty);
+        for cell in new_placement.cells() {
+            grid[&cell] = Some(entity);
+            dirty.send(DirtyGridEvent::<T>(cell, PhantomData));
+        }
+        placements.placed.insert(entity, new_placement);
     }
 }
 
 fn add_to_grid<T: Component>(
     mut grid: ResMut<Grid<T>>,
-    query: Query<(Entity, &GridLocation), Added<T>>,
+    mut placements: ResMut<Placements<T>>,
+    query: Query<(Entity, &GridLocation, Option<&Footprint>), Added<T>>,
     mut dirty: EventWriter<DirtyGridEvent<T>>,
 ) {
-    for (entity, location) in &query {
-        if let Some(existing) = grid[location] {
-            if existing != entity {
+    for (entity, location, footprint) in &query {
+        let placement = Placement::new(location, footprint);
+        for cell in placement.cells() {
+            if !cell.in_bounds() {
+                warn!("Entity placed outside of the grid");
+                continue;
+            }
+            if let Some(existing) = grid[&cell] {
+                if existing == entity {
+                    continue;
+                }
                 warn!("Over-writing entity in grid");
-                dirty.send(DirtyGridEvent::<T>(
-                    location.clone(),
-                    PhantomData::default(),
-                ));
-                grid[location] = Some(entity);
             }
-        } else {
-            dirty.send(DirtyGridEvent::<T>(
-                location.clone(),
-                PhantomData::default(),
-            ));
-            grid[location] = Some(entity);
+            dirty.send(DirtyGridEvent::<T>(cell.clone(), PhantomData));
+            grid[&cell] = Some(entity);
         }
+        placements.placed.insert(entity, placement);
     }
 }
 
-fn all_points() -> Vec<GridLocation> {
-    (0..GRID_SIZE)
-        .flat_map(|x| (0..GRID_SIZE).map(move |y| GridLocation::new(x as u32, y as u32)))
+pub(crate) fn all_points() -> Vec<GridLocation> {
+    let size = grid_size();
+    (0..size)
+        .flat_map(move |x| (0..size).map(move |y| GridLocation::new(x as u32, y as u32)))
         .collect()
 }
 
 impl<T> Default for ConnectedComponents<T> {
     fn default() -> Self {
         Self {
-            components: Default::default(),
+            chunk_regions: Default::default(),
+            component_of: Default::default(),
+            sizes: Default::default(),
+            generation: None,
             _marker: Default::default(),
         }
     }
@@ -200,7 +518,7 @@ impl<T> Default for ConnectedComponents<T> {
 impl<T> Clone for Grid<T> {
     fn clone(&self) -> Self {
         Self {
-            entities: self.entities,
+            entities: self.entities.clone(),
             _marker: self._marker,
         }
     }
@@ -209,7 +527,7 @@ impl<T> Clone for Grid<T> {
 impl<T> Default for Grid<T> {
     fn default() -> Self {
         Self {
-            entities: [[None; GRID_SIZE]; GRID_SIZE],
+            entities: ChunkedStorage::new(None),
             _marker: Default::default(),
         }
     }
@@ -223,12 +541,26 @@ impl GridLocation {
     pub fn from_world(position: Vec2) -> Option<Self> {
         let position = position + Vec2::splat(0.5);
         let location = GridLocation(IVec2::new(position.x as i32, position.y as i32));
-        if Grid::<()>::valid_index(&location) {
+        if location.in_bounds() {
             Some(location)
         } else {
             None
         }
     }
+
+    pub fn in_bounds(&self) -> bool {
+        let size = grid_size() as i32;
+        self.x >= 0 && self.y >= 0 && self.x < size && self.y < size
+    }
+
+    // In bounds left, down, right and up neighbors, shared by all grid types
+    pub fn neighbors(&self) -> Vec<GridLocation> {
+        [IVec2::NEG_X, IVec2::NEG_Y, IVec2::X, IVec2::Y]
+            .into_iter()
+            .map(|offset| GridLocation(self.0 + offset))
+            .filter(|location| location.in_bounds())
+            .collect()
+    }
 }
 
 impl From<IVec2> for GridLocation {
@@ -238,15 +570,15 @@ impl From<IVec2> for GridLocation {
 }
 
 impl<T> Grid<T> {
+    // Cells in chunks written out to disk count as occupied, nothing paths or builds there
+    // until a pawn comes close enough to load them
     pub fn occupied(&self, location: &GridLocation) -> bool {
-        Grid::<T>::valid_index(location) && self[location].is_some()
+        Grid::<T>::valid_index(location)
+            && (self.entities.is_unloaded(&location.chunk()) || self[location].is_some())
     }
 
     pub fn valid_index(location: &GridLocation) -> bool {
-        location.x >= 0
-            && location.y >= 0
-            && location.x < GRID_SIZE as i32
-            && location.y < GRID_SIZE as i32
+        location.in_bounds()
     }
 }
 
@@ -254,15 +586,7 @@ impl<T> Grid<T> {
     pub fn iter(&self) -> impl Iterator<Item = (Entity, GridLocation)> + '_ {
         self.entities
             .iter()
-            .flatten()
-            .enumerate()
-            .filter(|(_i, entity)| entity.is_some())
-            .map(|(i, entity)| {
-                (
-                    entity.unwrap(),
-                    GridLocation::new(i as u32 / GRID_SIZE as u32, i as u32 % GRID_SIZE as u32),
-                )
-            })
+            .filter_map(|(location, entity)| entity.map(|entity| (entity, location)))
     }
 }
 
@@ -270,27 +594,78 @@ impl<T> Index<&GridLocation> for Grid<T> {
     type Output = Option<Entity>;
 
     fn index(&self, index: &GridLocation) -> &Self::Output {
-        &self.entities[index.x as usize][index.y as usize]
+        self.entities.get(index)
     }
 }
 
+// Panics outside the grid
 impl<T> IndexMut<&GridLocation> for Grid<T> {
     fn index_mut(&mut self, index: &GridLocation) -> &mut Self::Output {
-        &mut self.entities[index.x as usize][index.y as usize]
+        self.entities.get_mut(index)
+    }
+}
+
+impl<T: Send + Sync + 'static> ChunkedGrid for Grid<T> {
+    type Value = Option<Entity>;
+
+    fn storage(&self) -> &ChunkedStorage<Option<Entity>> {
+        &self.entities
+    }
+
+    fn storage_mut(&mut self) -> &mut ChunkedStorage<Option<Entity>> {
+        &mut self.entities
     }
 }
 
 impl<T> ConnectedComponents<T> {
-    pub fn point_to_component(&self, start: &GridLocation) -> Option<&HashSet<GridLocation>> {
-        self.components
-            .iter()
-            .find(|component| component.contains(start))
+    // Storage generation these components were computed from
+    pub fn generation(&self) -> Option<u64> {
+        self.generation
+    }
+
+    // None for blocked cells and before the first computation finishes
+    pub fn component(&self, location: &GridLocation) -> Option<usize> {
+        let chunk = location.chunk();
+        let region = self.chunk_regions.get(&chunk)?.region_of[location.index_in_chunk()]?;
+        Some(self.component_of[&chunk][region])
+    }
+
+    // Cells in the component the location is in
+    pub fn component_size(&self, location: &GridLocation) -> Option<usize> {
+        self.component(location)
+            .map(|component| self.sizes[component])
+    }
+
+    pub fn count(&self) -> usize {
+        self.sizes.len()
     }
 
     pub fn in_same_component(&self, start: &GridLocation, end: &GridLocation) -> bool {
-        self.point_to_component(start) == self.point_to_component(end)
+        self.component(start) == self.component(end)
     }
 
+    // Every cell of a component, in location order
+    pub fn cells(&self, component: usize) -> Vec<GridLocation> {
+        let mut cells = self
+            .chunk_regions
+            .iter()
+            .filter(|(chunk, _)| self.component_of[*chunk].contains(&component))
+            .flat_map(|(chunk, regions)| {
+                regions
+                    .region_of
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, region)| {
+                        region.is_some_and(|region| self.component_of[chunk][region] == component)
+                    })
+                    .map(|(i, _)| chunk.cell(i))
+            })
+            .collect::<Vec<_>>();
+        cells.sort_unstable_by_key(|location| (location.x, location.y));
+        cells
+    }
+
+    // Points are picked by location, never by hash order, to keep the same seed giving the same result
     pub fn random_point_in_same_component<R>(
         &self,
         start: &GridLocation,
@@ -299,7 +674,83 @@ impl<T> ConnectedComponents<T> {
     where
         R: Rng + ?Sized,
     {
-        self.point_to_component(start)
-            .and_then(|component| component.iter().choose(rng).cloned())
+        let component = self.component(start)?;
+        // Big components are cheap to hit by sampling the whole map
+        for _ in 0..32 {
+            let guess = GridLocation::new(
+                rng.gen_range(0..grid_size() as u32),
+                rng.gen_range(0..grid_size() as u32),
+            );
+            if self.component(&guess) == Some(component) {
+                return Some(guess);
+            }
+        }
+        self.cells(component).choose(rng).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connected_components_join_across_chunks() {
+        let mut grid: Grid<()> = Grid::default();
+        let far = GridLocation::new(grid_size() as u32 - 1, 0);
+        let components = compute_connected_components(&grid, &HashMap::new(), None);
+        assert_eq!(components.count(), 1);
+        assert_eq!(
+            components.component_size(&far),
+            Some(grid_size() * grid_size())
+        );
+
+        for y in 0..grid_size() as u32 {
+            grid[&GridLocation::new(50, y)] = Some(Entity::from_raw(0));
+        }
+        let components =
+            compute_connected_components(&grid, &components.chunk_regions, components.generation);
+        assert_eq!(components.count(), 2);
+        assert!(!components.in_same_component(&GridLocation::new(0, 0), &far));
+        assert_eq!(components.cells(0).len(), 50 * grid_size());
+
+        grid[&GridLocation::new(50, 100)] = None;
+        let components =
+            compute_connected_components(&grid, &components.chunk_regions, components.generation);
+        assert_eq!(components.count(), 1);
+        assert!(components.in_same_component(&GridLocation::new(0, 0), &far));
+    }
+
+    #[test]
+    fn unloaded_chunks_have_no_components() {
+        let directory = std::env::temp_dir().join(format!(
+            "grid_chunks_{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let mut grid: Grid<()> = Grid::default();
+        let inside = GridLocation::new(40, 70);
+        grid[&inside] = Some(Entity::from_raw(0));
+        let components = compute_connected_components(&grid, &HashMap::new(), None);
+        assert!(components.component(&GridLocation::new(41, 70)).is_some());
+
+        grid.entities.unload(inside.chunk(), &directory).unwrap();
+        let components =
+            compute_connected_components(&grid, &components.chunk_regions, components.generation);
+        for cell in inside.chunk().cells() {
+            assert!(grid.occupied(&cell));
+            assert_eq!(components.component(&cell), None);
+        }
+
+        // Cleared while on disk, as when the building is removed far from every pawn
+        grid[&inside] = None;
+        grid.entities.load(inside.chunk(), &directory).unwrap();
+        assert!(!grid.occupied(&inside));
+        let components =
+            compute_connected_components(&grid, &components.chunk_regions, components.generation);
+        assert!(components.in_same_component(&inside, &GridLocation::new(0, 0)));
+        let _ = std::fs::remove_dir_all(directory);
     }
 }
@@ -0,0 +1,155 @@
+use crate::prelude::*;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DeathEvent>()
+            .add_systems(
+                SimulationTick,
+                (
+                    apply_starvation,
+                    kill_pawns.after(apply_starvation),
+                    grieve.after(kill_pawns),
+                ),
+            )
+            .add_systems(Update, show_corpses);
+    }
+}
+
+pub const MAX_HEALTH: f32 = 100.0;
+// Below this a pawn collapses and can't do anything until it heals
+pub const INCAPACITATED_HEALTH: f32 = 25.0;
+const STARVATION_DAMAGE: f32 = 2.0;
+// Only well fed pawns heal. Collapsed pawns rest, healing faster on any food at all since they
+// rely on someone else bringing it
+const REGENERATION: f32 = 0.5;
+const RESTING_REGENERATION: f32 = 2.0;
+const WELL_FED: f32 = 25.0;
+
+#[derive(Component)]
+pub struct Health {
+    pub value: f32,
+}
+
+// What is left of a pawn, dead pawns are despawned and replaced by one of these
+#[derive(Component)]
+pub struct Corpse {
+    pub died_at: u64,
+}
+
+#[derive(Event)]
+pub struct DeathEvent {
+    pub pawn: Entity,
+    pub position: Vec2,
+}
+
+fn apply_starvation(mut pawns: Query<(&mut Health, &Hunger)>, clock: Res<SimClock>) {
+    for (mut health, hunger) in &mut pawns {
+        if hunger.value <= 0.0 {
+            health.value -= STARVATION_DAMAGE * clock.delta_seconds();
+        } else if health.value < INCAPACITATED_HEALTH {
+            health.value += RESTING_REGENERATION * clock.delta_seconds();
+        } else if hunger.value > WELL_FED {
+            health.value = (health.value + REGENERATION * clock.delta_seconds()).min(MAX_HEALTH);
+        }
+    }
+}
+
+pub fn kill_pawns(
+    mut commands: Commands,
+    mut deaths: EventWriter<DeathEvent>,
+    pawns: Query<(Entity, &Health, &Transform, Option<&Name>), With<Pawn>>,
+    clock: Res<SimClock>,
+) {
+    for (pawn, health, transform, name) in &pawns {
+        if health.value > 0.0 {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        match name {
+            Some(name) => info!("{} died at {}", name, position),
+            None => info!("Pawn {} died at {}", pawn.index(), position),
+        }
+        commands.entity(pawn).despawn_recursive();
+        commands.spawn((
+            SpatialBundle::from_transform(
+                Transform::from_translation(position.extend(700.0))
+                    .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+            ),
+            CharacterSprite::default(),
+            Corpse {
+                died_at: clock.tick(),
+            },
+        ));
+        deaths.send(DeathEvent { pawn, position });
+    }
+}
+
+fn grieve(mut deaths: EventReader<DeathEvent>, mut survivors: Query<&mut Mood, With<Pawn>>) {
+    for _death in deaths.iter() {
+        for mut mood in &mut survivors {
+            mood.think(Thought::Grieving);
+        }
+    }
+}
+
+// The atlas sprite is attached a frame after the corpse spawns
+fn show_corpses(
+    mut corpses: Query<&mut TextureAtlasSprite, (With<Corpse>, Added<TextureAtlasSprite>)>,
+) {
+    for mut sprite in &mut corpses {
+        sprite.color = Color::GRAY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starving_pawn_dies_and_leaves_a_corpse() {
+        let mut app = App::new();
+        app.add_event::<DeathEvent>()
+            .init_resource::<SimClock>()
+            .add_systems(Update, (apply_starvation, kill_pawns, grieve).chain());
+        let spawn = |app: &mut App, health: f32, hunger: f32| {
+            app.world
+                .spawn((
+                    Pawn,
+                    Health { value: health },
+                    Hunger { value: hunger },
+                    Mood::default(),
+                    Transform::default(),
+                ))
+                .id()
+        };
+        let starving = spawn(&mut app, 0.01, 0.0);
+        let survivor = spawn(&mut app, MAX_HEALTH, 50.0);
+
+        app.update();
+        assert!(app.world.get_entity(starving).is_none());
+        assert_eq!(app.world.query::<&Corpse>().iter(&app.world).len(), 1);
+        let mood = app.world.get::<Mood>(survivor).unwrap();
+        assert!(mood.thoughts().any(|thought| thought == Thought::Grieving));
+    }
+
+    #[test]
+    fn collapsed_pawns_heal_on_any_food() {
+        let mut app = App::new();
+        app.init_resource::<SimClock>()
+            .add_systems(Update, apply_starvation);
+        let collapsed = app
+            .world
+            .spawn((Health { value: 10.0 }, Hunger { value: 5.0 }))
+            .id();
+        let standing = app
+            .world
+            .spawn((Health { value: 50.0 }, Hunger { value: 5.0 }))
+            .id();
+
+        app.update();
+        assert!(app.world.get::<Health>(collapsed).unwrap().value > 10.0);
+        assert_eq!(app.world.get::<Health>(standing).unwrap().value, 50.0);
+    }
+}
@@ -0,0 +1,426 @@
+use crate::prelude::*;
+use bevy::ecs::system::SystemParam;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>()
+            .add_systems(Update, queue_undo_redo);
+    }
+}
+
+// Everything needed to spawn a player built structure again
+#[derive(Clone, Debug)]
+pub enum Blueprint {
+    Wall {
+        location: GridLocation,
+    },
+    FoodMachine {
+        definition: BuildingDefinition,
+        location: GridLocation,
+        rotation: Rotation,
+        rate: f32,
+    },
+    Bed {
+        location: GridLocation,
+        rotation: Rotation,
+    },
+}
+
+impl Blueprint {
+    pub fn cost(&self) -> u32 {
+        match self {
+            Blueprint::Wall { .. } => WALL_COST,
+            Blueprint::FoodMachine { definition, .. } => definition.cost,
+            Blueprint::Bed { .. } => BuildingDefinition::bed().cost,
+        }
+    }
+
+    pub fn cells(&self) -> Vec<GridLocation> {
+        match self {
+            Blueprint::Wall { location } => vec![location.clone()],
+            Blueprint::FoodMachine {
+                definition,
+                location,
+                rotation,
+                ..
+            } => definition
+                .footprint(*rotation)
+                .into_iter()
+                .map(|offset| GridLocation(location.0 + offset))
+                .collect(),
+            Blueprint::Bed { location, rotation } => BuildingDefinition::bed()
+                .footprint(*rotation)
+                .into_iter()
+                .map(|offset| GridLocation(location.0 + offset))
+                .collect(),
+        }
+    }
+
+    // Use tiles this blueprint adds for pawns to eat at
+    pub fn food_tiles(&self) -> Vec<GridLocation> {
+        match self {
+            Blueprint::Wall { .. } | Blueprint::Bed { .. } => Vec::new(),
+            Blueprint::FoodMachine {
+                definition,
+                location,
+                rotation,
+                ..
+            } => definition
+                .use_offsets(*rotation)
+                .into_iter()
+                .map(|offset| GridLocation(location.0 + offset))
+                .collect(),
+        }
+    }
+
+    pub fn can_place(&self, layers: &TileLayers) -> bool {
+        match self {
+            Blueprint::Wall { location } => layers.can_place(TileLayer::Structure, location),
+            Blueprint::FoodMachine {
+                definition,
+                location,
+                rotation,
+                ..
+            } => definition.can_place(layers, location, *rotation),
+            Blueprint::Bed { location, rotation } => {
+                BuildingDefinition::bed().can_place(layers, location, *rotation)
+            }
+        }
+    }
+
+    pub fn spawn(&self, commands: &mut Commands) -> Entity {
+        match self {
+            Blueprint::Wall { location } => spawn_wall(commands, location.clone()),
+            Blueprint::FoodMachine {
+                definition,
+                location,
+                rotation,
+                rate,
+            } => {
+                let machine = definition.spawn(commands, location.clone(), *rotation);
+                commands.entity(machine).insert(FoodMachine {
+                    rate: *rate,
+                    cooked: definition.cooks,
+                });
+                machine
+            }
+            Blueprint::Bed { location, rotation } => {
+                let bed = BuildingDefinition::bed().spawn(commands, location.clone(), *rotation);
+                commands.entity(bed).insert(Bed);
+                bed
+            }
+        }
+    }
+}
+
+// Cells the entity already covers count as free, so buildings can shift over themselves
+pub fn can_move_to(
+    layers: &TileLayers,
+    entity: Entity,
+    to: &GridLocation,
+    footprint: Option<&Footprint>,
+) -> bool {
+    covered_cells(to, footprint).iter().all(|cell| {
+        layers.can_place(TileLayer::Structure, cell)
+            || layers.get(TileLayer::Structure, cell) == Some(entity)
+    })
+}
+
+// A reversible player action
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Build(Vec<(Blueprint, Entity)>),
+    Designate {
+        entities: Vec<Entity>,
+        designate: bool,
+    },
+    Move {
+        entity: Entity,
+        from: GridLocation,
+        to: GridLocation,
+    },
+}
+
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl EditHistory {
+    pub fn record(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    // Redoing a build spawns new entities, older edits have to point at them instead
+    fn remap(&mut self, old: Entity, new: Entity) {
+        let swap = |entity: &mut Entity| {
+            if *entity == old {
+                *entity = new;
+            }
+        };
+        for edit in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            match edit {
+                Edit::Build(built) => built.iter_mut().for_each(|(_, entity)| swap(entity)),
+                Edit::Designate { entities, .. } => entities.iter_mut().for_each(swap),
+                Edit::Move { entity, .. } => swap(entity),
+            }
+        }
+    }
+}
+
+// Ctrl+Z and Ctrl+Y, applied with the other player actions on the next tick
+fn queue_undo_redo(keyboard: Res<Input<KeyCode>>, mut actions: ResMut<PlayerActions>) {
+    if !keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Z) {
+        actions.push(PlayerAction::Undo);
+    } else if keyboard.just_pressed(KeyCode::Y) {
+        actions.push(PlayerAction::Redo);
+    }
+}
+
+// Everything applying a player edit touches, only used at tick boundaries so edits land on the
+// same tick in every run
+#[derive(SystemParam)]
+pub struct Editor<'w, 's> {
+    commands: Commands<'w, 's>,
+    history: ResMut<'w, EditHistory>,
+    stockpile: ResMut<'w, Stockpile>,
+    placement: PlacementCheck<'w, 's>,
+    locations: Query<'w, 's, (&'static GridLocation, Option<&'static Footprint>), With<LockToGrid>>,
+    structures: Query<'w, 's, (), With<Structure>>,
+    designated: Query<'w, 's, (), With<Deconstruct>>,
+    brains: Query<'w, 's, &'static Brain>,
+}
+
+impl<'w, 's> Editor<'w, 's> {
+    // Cells are where each building of a drag is anchored, the ones that no longer fit are skipped
+    pub fn build(&mut self, mode: ClickMode, rotation: Rotation, cells: Vec<GridLocation>) {
+        let blueprints = mode.blueprints(cells, rotation, &self.placement.layers);
+        if !self.placement.allows_batch(&blueprints) {
+            warn!("That would trap a pawn");
+            return;
+        }
+        let cost = blueprints
+            .iter()
+            .map(|blueprint| blueprint.cost())
+            .sum::<u32>();
+        if cost > self.stockpile.materials {
+            warn!(
+                "Not enough materials for {} {}",
+                blueprints.len(),
+                mode.label()
+            );
+            return;
+        }
+        self.stockpile.materials -= cost;
+        let built = blueprints
+            .into_iter()
+            .map(|blueprint| {
+                let entity = blueprint.spawn(&mut self.commands);
+                (blueprint, entity)
+            })
+            .collect::<Vec<_>>();
+        if !built.is_empty() {
+            self.history.record(Edit::Build(built));
+        }
+    }
+
+    // The map outline isn't in the structure grid so it can never be designated
+    pub fn designate(&mut self, cells: &[GridLocation], designate: bool) {
+        let mut entities = Vec::new();
+        for cell in cells {
+            let entity = match self.placement.layers.get(TileLayer::Structure, cell) {
+                Some(entity) => entity,
+                None => continue,
+            };
+            if entities.contains(&entity) || self.designated.contains(entity) == designate {
+                continue;
+            }
+            entities.push(entity);
+        }
+        if entities.is_empty() {
+            return;
+        }
+        set_designation(&mut self.commands, &self.structures, &entities, designate);
+        self.history.record(Edit::Designate {
+            entities,
+            designate,
+        });
+    }
+
+    // Moves whatever covers `grabbed` so that cell ends up on `dropped`. Checked here so only
+    // moves that happen are recorded, the grid plugins then update the grids
+    pub fn move_building(&mut self, grabbed: &GridLocation, dropped: &GridLocation) {
+        let entity = match self.placement.layers.get(TileLayer::Structure, grabbed) {
+            Some(entity) => entity,
+            None => return,
+        };
+        let (from, footprint) = match self.locations.get(entity) {
+            Ok(found) => found,
+            Err(_) => return,
+        };
+        let to = GridLocation(from.0 + dropped.0 - grabbed.0);
+        if *from == to {
+            return;
+        }
+        if !can_move_to(&self.placement.layers, entity, &to, footprint) {
+            warn!("Can't move that there");
+            return;
+        }
+        self.history.record(Edit::Move {
+            entity,
+            from: from.clone(),
+            to: to.clone(),
+        });
+        self.commands.entity(entity).insert(to);
+    }
+
+    pub fn undo(&mut self) {
+        let edit = match self.history.undo.pop() {
+            Some(edit) => edit,
+            None => return,
+        };
+        match &edit {
+            Edit::Build(built) => {
+                // Same as deconstructing, machines are never pulled out from under a pawn
+                if built.iter().any(|(_, entity)| self.in_use(*entity)) {
+                    warn!("A pawn is using that, undo again once they're done");
+                    self.history.undo.push(edit);
+                    return;
+                }
+                // Anything already deconstructed or destroyed has nothing left to undo
+                for (blueprint, entity) in built {
+                    if self.structures.contains(*entity) {
+                        self.stockpile.materials += blueprint.cost();
+                        self.commands.entity(*entity).despawn_recursive();
+                    }
+                }
+            }
+            Edit::Designate {
+                entities,
+                designate,
+            } => set_designation(&mut self.commands, &self.structures, entities, !designate),
+            Edit::Move { entity, from, .. } => {
+                if !self.relocate(*entity, from) {
+                    warn!("Something is in the way of undoing that move");
+                    self.history.undo.push(edit);
+                    return;
+                }
+            }
+        }
+        self.history.redo.push(edit);
+    }
+
+    fn in_use(&self, entity: Entity) -> bool {
+        self.brains.iter().any(
+            |brain| matches!(brain.state(), BrainState::OperateMachine(machine) if *machine == entity),
+        )
+    }
+
+    pub fn redo(&mut self) {
+        let mut edit = match self.history.redo.pop() {
+            Some(edit) => edit,
+            None => return,
+        };
+        match &mut edit {
+            Edit::Build(built) => {
+                let cost: u32 = built.iter().map(|(blueprint, _)| blueprint.cost()).sum();
+                if cost > self.stockpile.materials {
+                    warn!("Not enough materials to redo");
+                    self.history.redo.push(edit);
+                    return;
+                }
+                for (blueprint, entity) in built.iter_mut() {
+                    if !blueprint.can_place(&self.placement.layers) {
+                        continue;
+                    }
+                    self.stockpile.materials -= blueprint.cost();
+                    let new = blueprint.spawn(&mut self.commands);
+                    self.history.remap(*entity, new);
+                    *entity = new;
+                }
+            }
+            Edit::Designate {
+                entities,
+                designate,
+            } => set_designation(&mut self.commands, &self.structures, entities, *designate),
+            Edit::Move { entity, to, .. } => {
+                if !self.relocate(*entity, to) {
+                    warn!("Something is in the way of redoing that move");
+                    self.history.redo.push(edit);
+                    return;
+                }
+            }
+        }
+        self.history.undo.push(edit);
+    }
+
+    // False when something else now covers the cells, gone entities have nothing to move
+    fn relocate(&mut self, entity: Entity, to: &GridLocation) -> bool {
+        let footprint = match self.locations.get(entity) {
+            Ok((_, footprint)) => footprint,
+            Err(_) => return true,
+        };
+        if !can_move_to(&self.placement.layers, entity, to, footprint) {
+            return false;
+        }
+        self.commands.entity(entity).insert(to.clone());
+        true
+    }
+}
+
+fn set_designation(
+    commands: &mut Commands,
+    structures: &Query<(), With<Structure>>,
+    entities: &[Entity],
+    designate: bool,
+) {
+    for entity in entities
+        .iter()
+        .filter(|entity| structures.contains(**entity))
+    {
+        if designate {
+            commands.entity(*entity).insert(Deconstruct::default());
+        } else {
+            commands.entity(*entity).remove::<Deconstruct>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redone_builds_remap_later_edits() {
+        let old = Entity::from_raw(1);
+        let new = Entity::from_raw(2);
+        let mut history = EditHistory::default();
+        history.record(Edit::Build(vec![(
+            Blueprint::Wall {
+                location: GridLocation::new(1, 1),
+            },
+            old,
+        )]));
+        history.redo.push(Edit::Designate {
+            entities: vec![old],
+            designate: true,
+        });
+
+        history.remap(old, new);
+        assert!(matches!(&history.undo[0], Edit::Build(built) if built[0].1 == new));
+        assert!(matches!(&history.redo[0], Edit::Designate { entities, .. } if entities[0] == new));
+
+        history.record(Edit::Designate {
+            entities: vec![new],
+            designate: false,
+        });
+        assert!(history.redo.is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::prelude::*;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud).add_systems(
+            Update,
+            (
+                click_toolbar,
+                update_toolbar,
+                click_speed_controls,
+                update_speed_controls,
+                update_colony_summary,
+                sync_need_rows,
+                update_need_bars,
+            ),
+        );
+    }
+}
+
+const TOOLBAR: [(ClickMode, &str); 6] = [
+    (ClickMode::None, "1 Select"),
+    (ClickMode::BuildWall, "2 Wall"),
+    (ClickMode::BuildFoodMachine, "3 Food"),
+    (ClickMode::Move, "4 Move"),
+    (ClickMode::BuildKitchen, "5 Kitchen"),
+    (ClickMode::BuildBed, "6 Bed"),
+];
+
+const PANEL_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.8);
+pub(crate) const BUTTON_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const SELECTED_COLOR: Color = Color::rgb(0.35, 0.55, 0.35);
+const BAR_WIDTH: f32 = 100.0;
+
+#[derive(Component)]
+struct ToolbarButton(ClickMode);
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum SpeedControl {
+    Pause,
+    Speed(f32),
+    Step,
+}
+
+#[derive(Component)]
+struct ColonySummary;
+
+// Panel holding one NeedRow per pawn
+#[derive(Component)]
+struct NeedPanel;
+
+#[derive(Component)]
+struct NeedRow(Entity);
+
+#[derive(Clone, Copy)]
+enum Need {
+    Hunger,
+    Recreation,
+}
+
+#[derive(Component)]
+struct NeedFill {
+    pawn: Entity,
+    need: Need,
+}
+
+pub(crate) fn text_style() -> TextStyle {
+    TextStyle {
+        font_size: 18.0,
+        ..default()
+    }
+}
+
+// Panels get an Interaction so hovering them sets CursorPosition::over_ui
+pub(crate) fn panel(style: Style) -> (NodeBundle, Interaction) {
+    (
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(6.0)),
+                ..style
+            },
+            background_color: PANEL_COLOR.into(),
+            ..default()
+        },
+        Interaction::default(),
+    )
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn(panel(Style {
+            bottom: Val::Px(8.0),
+            left: Val::Px(8.0),
+            column_gap: Val::Px(6.0),
+            ..default()
+        }))
+        .with_children(|toolbar| {
+            for (mode, label) in TOOLBAR {
+                toolbar
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::all(Val::Px(6.0)),
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                        ToolbarButton(mode),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(label, text_style()));
+                    });
+            }
+        });
+
+    commands
+        .spawn(panel(Style {
+            bottom: Val::Px(8.0),
+            left: Val::Percent(50.0),
+            column_gap: Val::Px(6.0),
+            ..default()
+        }))
+        .with_children(|controls| {
+            let speeds = SPEEDS.map(SpeedControl::Speed);
+            for control in [SpeedControl::Pause]
+                .into_iter()
+                .chain(speeds)
+                .chain([SpeedControl::Step])
+            {
+                let label = match control {
+                    SpeedControl::Pause => "Pause".to_string(),
+                    SpeedControl::Speed(speed) => format!("{}x", speed),
+                    SpeedControl::Step => "Step".to_string(),
+                };
+                controls
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::all(Val::Px(6.0)),
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                        control,
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(label, text_style()));
+                    });
+            }
+        });
+
+    commands
+        .spawn(panel(Style {
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        }))
+        .with_children(|summary| {
+            summary.spawn((TextBundle::from_section("", text_style()), ColonySummary));
+        });
+
+    commands.spawn((
+        panel(Style {
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.0),
+            ..default()
+        }),
+        NeedPanel,
+    ));
+}
+
+fn click_toolbar(
+    buttons: Query<(&Interaction, &ToolbarButton), Changed<Interaction>>,
+    mut mode: ResMut<ClickMode>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            *mode = button.0;
+        }
+    }
+}
+
+// Keys can change the mode too so the toolbar follows the resource rather than the clicks
+fn update_toolbar(
+    mut buttons: Query<(&ToolbarButton, &mut BackgroundColor)>,
+    mode: Res<ClickMode>,
+) {
+    if !mode.is_changed() {
+        return;
+    }
+    for (button, mut color) in &mut buttons {
+        color.0 = if button.0 == *mode {
+            SELECTED_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+}
+
+fn click_speed_controls(
+    buttons: Query<(&Interaction, &SpeedControl), Changed<Interaction>>,
+    mut clock: ResMut<SimClock>,
+) {
+    for (interaction, control) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match control {
+            SpeedControl::Pause => clock.paused = !clock.paused,
+            SpeedControl::Speed(speed) => {
+                clock.speed = *speed;
+                clock.paused = false;
+            }
+            SpeedControl::Step => clock.step(),
+        }
+    }
+}
+
+fn update_speed_controls(
+    mut buttons: Query<(&SpeedControl, &mut BackgroundColor)>,
+    clock: Res<SimClock>,
+) {
+    if !clock.is_changed() {
+        return;
+    }
+    for (control, mut color) in &mut buttons {
+        let selected = match control {
+            SpeedControl::Pause => clock.paused,
+            SpeedControl::Speed(speed) => !clock.paused && clock.speed == *speed,
+            SpeedControl::Step => false,
+        };
+        color.0 = if selected {
+            SELECTED_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+}
+
+fn update_colony_summary(
+    mut summary: Query<&mut Text, With<ColonySummary>>,
+    pawns: Query<(&Hunger, &Recreation), With<Pawn>>,
+    corpses: Query<(), With<Corpse>>,
+    food_machines: Query<(), With<FoodMachine>>,
+    beds: Query<(), With<Bed>>,
+    stockpile: Res<Stockpile>,
+) {
+    let count = pawns.iter().len();
+    let (hunger, recreation) = pawns.iter().fold(
+        (0.0, 0.0),
+        |(hunger, recreation), (pawn_hunger, pawn_recreation)| {
+            (
+                hunger + pawn_hunger.value,
+                recreation + pawn_recreation.value,
+            )
+        },
+    );
+    let average = |total: f32| {
+        if count == 0 {
+            0.0
+        } else {
+            total / count as f32
+        }
+    };
+
+    summary.single_mut().sections[0].value = format!(
+        "Pawns: {} / {} beds ({} dead)\nHunger: {:.0}\nRecreation: {:.0}\nFood machines: {}\nMaterials: {}",
+        count,
+        beds.iter().len(),
+        corpses.iter().len(),
+        average(hunger),
+        average(recreation),
+        food_machines.iter().len(),
+        stockpile.materials
+    );
+}
+
+fn need_bar(parent: &mut ChildBuilder, pawn: Entity, need: Need, color: Color) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_WIDTH),
+                height: Val::Px(6.0),
+                ..default()
+            },
+            background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+            ..default()
+        })
+        .with_children(|bar| {
+            bar.spawn((
+                NodeBundle {
+                    style: Style {
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                NeedFill { pawn, need },
+            ));
+        });
+}
+
+// Pawns can come and go so rows are added and removed to match
+fn sync_need_rows(
+    mut commands: Commands,
+    panel: Query<Entity, With<NeedPanel>>,
+    rows: Query<(Entity, &NeedRow)>,
+    pawns: Query<(Entity, &Name), With<Pawn>>,
+) {
+    for (row, pawn) in &rows {
+        if !pawns.contains(pawn.0) {
+            commands.entity(row).despawn_recursive();
+        }
+    }
+
+    let panel = panel.single();
+    for (pawn, name) in &pawns {
+        if rows.iter().any(|(_, row)| row.0 == pawn) {
+            continue;
+        }
+        let row = commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(2.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                NeedRow(pawn),
+            ))
+            .with_children(|row| {
+                row.spawn(TextBundle::from_section(
+                    name.to_string(),
+                    TextStyle {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                ));
+                need_bar(row, pawn, Need::Hunger, Color::ORANGE);
+                need_bar(row, pawn, Need::Recreation, Color::BLUE);
+            })
+            .id();
+        commands.entity(panel).add_child(row);
+    }
+}
+
+fn update_need_bars(
+    mut fills: Query<(&NeedFill, &mut Style)>,
+    pawns: Query<(&Hunger, &Recreation)>,
+) {
+    for (fill, mut style) in &mut fills {
+        if let Ok((hunger, recreation)) = pawns.get(fill.pawn) {
+            let value = match fill.need {
+                Need::Hunger => hunger.value,
+                Need::Recreation => recreation.value,
+            };
+            style.width = Val::Percent(value.clamp(0.0, 100.0));
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+const FIRST_NAMES: [&str; 16] = [
+    "Ada", "Bram", "Cleo", "Dov", "Edda", "Finn", "Greta", "Hal", "Ines", "Jory", "Kit", "Lio",
+    "Mara", "Nils", "Orla", "Pim",
+];
+const LAST_NAMES: [&str; 12] = [
+    "Ashby", "Birch", "Cole", "Dunmore", "Fenn", "Hale", "Kerr", "Lusk", "Marsh", "Pike", "Rook",
+    "Vance",
+];
+// Chance of each trait on a generated pawn
+const TRAIT_CHANCE: f64 = 0.25;
+// Seconds of work for the first level, each level after needs more
+const LEVEL_XP: f32 = 30.0;
+pub const MAX_SKILL_LEVEL: u32 = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Trait {
+    FastWalker,
+    BigAppetite,
+    // Works slower and gets bored faster
+    Lazy,
+}
+
+const ALL_TRAITS: [Trait; 3] = [Trait::FastWalker, Trait::BigAppetite, Trait::Lazy];
+
+#[derive(Component, Clone, Default, Debug)]
+pub struct Traits(pub Vec<Trait>);
+
+impl Traits {
+    fn product(&self, effect: impl Fn(&Trait) -> f32) -> f32 {
+        self.0.iter().map(effect).product()
+    }
+
+    pub fn walk_speed(&self) -> f32 {
+        self.product(|pawn_trait| match pawn_trait {
+            Trait::FastWalker => 1.5,
+            _ => 1.0,
+        })
+    }
+
+    pub fn hunger_rate(&self) -> f32 {
+        self.product(|pawn_trait| match pawn_trait {
+            Trait::BigAppetite => 1.5,
+            _ => 1.0,
+        })
+    }
+
+    pub fn recreation_rate(&self) -> f32 {
+        self.product(|pawn_trait| match pawn_trait {
+            Trait::Lazy => 1.25,
+            _ => 1.0,
+        })
+    }
+
+    pub fn work_speed(&self) -> f32 {
+        self.product(|pawn_trait| match pawn_trait {
+            Trait::Lazy => 0.7,
+            _ => 1.0,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SkillKind {
+    // Repairing and deconstructing
+    Construction,
+    Cooking,
+}
+
+// Experience is seconds spent doing the job
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Skill {
+    pub xp: f32,
+}
+
+impl Skill {
+    // Level n needs n² * LEVEL_XP
+    pub fn level(&self) -> u32 {
+        ((self.xp / LEVEL_XP).sqrt() as u32).min(MAX_SKILL_LEVEL)
+    }
+
+    // Up to twice as fast at the top level
+    pub fn speed(&self) -> f32 {
+        1.0 + self.level() as f32 / MAX_SKILL_LEVEL as f32
+    }
+
+    // 0 to 1, how good whatever comes out of the job is
+    pub fn quality(&self) -> f32 {
+        self.level() as f32 / MAX_SKILL_LEVEL as f32
+    }
+}
+
+#[derive(Component, Clone, Default, Debug)]
+pub struct Skills {
+    pub construction: Skill,
+    pub cooking: Skill,
+}
+
+impl Skills {
+    pub fn get(&self, kind: SkillKind) -> &Skill {
+        match kind {
+            SkillKind::Construction => &self.construction,
+            SkillKind::Cooking => &self.cooking,
+        }
+    }
+
+    // Returns the new level when this practice levelled the skill up
+    pub fn practice(&mut self, kind: SkillKind, seconds: f32) -> Option<u32> {
+        let skill = match kind {
+            SkillKind::Construction => &mut self.construction,
+            SkillKind::Cooking => &mut self.cooking,
+        };
+        let before = skill.level();
+        skill.xp += seconds;
+        Some(skill.level()).filter(|level| *level > before)
+    }
+}
+
+pub fn random_name<R: Rng + ?Sized>(rng: &mut R) -> Name {
+    Name::new(format!(
+        "{} {}",
+        FIRST_NAMES.choose(rng).unwrap(),
+        LAST_NAMES.choose(rng).unwrap()
+    ))
+}
+
+pub fn random_traits<R: Rng + ?Sized>(rng: &mut R) -> Traits {
+    Traits(
+        ALL_TRAITS
+            .into_iter()
+            .filter(|_| rng.gen_bool(TRAIT_CHANCE))
+            .collect(),
+    )
+}
+
+// Some pawns arrive knowing a bit already
+pub fn random_skills<R: Rng + ?Sized>(rng: &mut R) -> Skills {
+    let mut skill = || Skill {
+        xp: rng.gen_range(0..=4u32).pow(2) as f32 * LEVEL_XP,
+    };
+    Skills {
+        construction: skill(),
+        cooking: skill(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skills_level_with_practice() {
+        let mut skills = Skills::default();
+        assert_eq!(skills.practice(SkillKind::Cooking, LEVEL_XP - 1.0), None);
+        assert_eq!(skills.practice(SkillKind::Cooking, 1.0), Some(1));
+        assert_eq!(skills.practice(SkillKind::Cooking, 3.0 * LEVEL_XP), Some(2));
+        assert_eq!(skills.get(SkillKind::Construction).level(), 0);
+
+        skills.cooking.xp = 1000.0 * LEVEL_XP;
+        assert_eq!(skills.cooking.level(), MAX_SKILL_LEVEL);
+        assert_eq!(skills.cooking.speed(), 2.0);
+
+        let traits = Traits(vec![Trait::FastWalker, Trait::Lazy]);
+        assert_eq!(traits.walk_speed(), 1.5);
+        assert_eq!(traits.hunger_rate(), 1.0);
+    }
+}
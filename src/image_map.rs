@@ -0,0 +1,210 @@
+use std::{fmt, fs, io, path::PathBuf};
+
+use crate::prelude::*;
+use image::RgbaImage;
+use serde::Deserialize;
+
+// Start with `--map <file>` to play a hand made level instead of a generated one. PNGs are read
+// through the palette at `--palette <file>`, anything else is treated as an ASCII map
+pub struct ImageMapPlugin {
+    map: Option<PathBuf>,
+    palette: PathBuf,
+}
+
+impl ImageMapPlugin {
+    pub fn from_args() -> Self {
+        let arg = |name: &str| {
+            std::env::args()
+                .skip_while(|arg| arg != name)
+                .nth(1)
+                .map(PathBuf::from)
+        };
+        Self {
+            map: arg("--map"),
+            palette: arg("--palette").unwrap_or_else(|| PathBuf::from(PALETTE_PATH)),
+        }
+    }
+}
+
+impl Plugin for ImageMapPlugin {
+    fn build(&self, app: &mut App) {
+        let path = match &self.map {
+            Some(path) => path,
+            None => return,
+        };
+        match load_map(path, &self.palette) {
+            Ok(map) => {
+                info!("Loaded map {:?}", path);
+                app.insert_resource(LoadedMap(map));
+            }
+            Err(err) => warn!("Failed to load map {:?}: {}", path, err),
+        }
+    }
+}
+
+pub(crate) const PALETTE_PATH: &str = "assets/maps/palette.ron";
+
+// Replaces the generated map when present
+#[derive(Resource)]
+pub struct LoadedMap(pub AsciiMap);
+
+// Colors are RGB hex strings, each mapped to the ASCII map glyph it stands for. Alpha is ignored
+#[derive(Deserialize, Debug)]
+pub struct Palette {
+    colors: HashMap<String, char>,
+}
+
+#[derive(Debug)]
+pub enum ImageMapError {
+    Io(io::Error),
+    Image(image::ImageError),
+    Palette(String),
+    UnknownColor { x: u32, y: u32, color: String },
+    TooLarge { width: u32, height: u32 },
+    // Pawns only start on spawn points, without one the colony would start empty
+    NoSpawn,
+    Ascii(AsciiMapError),
+}
+
+impl fmt::Display for ImageMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageMapError::Io(err) => write!(f, "{}", err),
+            ImageMapError::Image(err) => write!(f, "{}", err),
+            ImageMapError::Palette(err) => write!(f, "bad palette: {}", err),
+            ImageMapError::UnknownColor { x, y, color } => {
+                write!(
+                    f,
+                    "color #{} at pixel {}, {} is not in the palette",
+                    color, x, y
+                )
+            }
+            ImageMapError::TooLarge { width, height } => write!(
+                f,
+                "image is {}x{} but the grid is only {size}x{size}",
+                width,
+                height,
+                size = grid_size()
+            ),
+            ImageMapError::NoSpawn => write!(f, "map has no spawn point"),
+            ImageMapError::Ascii(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Palette {
+    pub fn parse(text: &str) -> Result<Self, ImageMapError> {
+        let palette: Palette =
+            ron::from_str(text).map_err(|err| ImageMapError::Palette(err.to_string()))?;
+        for (color, glyph) in &palette.colors {
+            if parse_color(color).is_none() {
+                return Err(ImageMapError::Palette(format!(
+                    "{:?} is not a color",
+                    color
+                )));
+            }
+            if !AsciiMap::default().place(GridLocation::new(0, 0), *glyph) {
+                return Err(ImageMapError::Palette(format!("{:?} is not a tile", glyph)));
+            }
+        }
+        Ok(palette)
+    }
+
+    fn glyph(&self, color: [u8; 3]) -> Option<char> {
+        self.colors
+            .iter()
+            .find(|(hex, _)| parse_color(hex) == Some(color))
+            .map(|(_, glyph)| *glyph)
+    }
+}
+
+fn parse_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+// The top row of the image is the top of the map, same as the ASCII format
+pub fn import_image(image: &RgbaImage, palette: &Palette) -> Result<AsciiMap, ImageMapError> {
+    let (width, height) = image.dimensions();
+    if width as usize > grid_size() || height as usize > grid_size() {
+        return Err(ImageMapError::TooLarge { width, height });
+    }
+
+    let mut map = AsciiMap::default();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let [r, g, b, _] = pixel.0;
+        let glyph = palette
+            .glyph([r, g, b])
+            .ok_or_else(|| ImageMapError::UnknownColor {
+                x,
+                y,
+                color: format!("{:02x}{:02x}{:02x}", r, g, b),
+            })?;
+        map.place(GridLocation::new(x, height - 1 - y), glyph);
+    }
+    require_spawn(map)
+}
+
+fn require_spawn(map: AsciiMap) -> Result<AsciiMap, ImageMapError> {
+    if map.spawns.is_empty() {
+        return Err(ImageMapError::NoSpawn);
+    }
+    Ok(map)
+}
+
+pub fn load_map(path: &PathBuf, palette: &PathBuf) -> Result<AsciiMap, ImageMapError> {
+    let is_png = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+    if !is_png {
+        let text = fs::read_to_string(path).map_err(ImageMapError::Io)?;
+        return require_spawn(AsciiMap::parse(&text).map_err(ImageMapError::Ascii)?);
+    }
+
+    let palette = Palette::parse(&fs::read_to_string(palette).map_err(ImageMapError::Io)?)?;
+    let image = image::open(path).map_err(ImageMapError::Image)?;
+    import_image(&image.to_rgba8(), &palette)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn pixels_map_through_the_palette() {
+        let palette = Palette::parse(
+            r##"(colors: { "#000000": '#', "ffffff": '.', "00ff00": 'P', "ff0000": '>' })"##,
+        )
+        .unwrap();
+        let image = RgbaImage::from_fn(3, 2, |x, y| match (x, y) {
+            (0, _) => Rgba([0, 0, 0, 255]),
+            (1, 1) => Rgba([0, 255, 0, 255]),
+            (2, 0) => Rgba([255, 0, 0, 255]),
+            _ => Rgba([255, 255, 255, 255]),
+        });
+
+        let map = import_image(&image, &palette).unwrap();
+        assert_eq!(map, AsciiMap::parse("#.>\n#P.").unwrap());
+
+        let mut image = image;
+        image.put_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        assert!(matches!(
+            import_image(&image, &palette),
+            Err(ImageMapError::NoSpawn)
+        ));
+        image.put_pixel(1, 0, Rgba([1, 2, 3, 255]));
+        assert!(matches!(
+            import_image(&image, &palette),
+            Err(ImageMapError::UnknownColor { x: 1, y: 0, .. })
+        ));
+        assert!(matches!(
+            import_image(&RgbaImage::new(grid_size() as u32 + 1, 1), &palette),
+            Err(ImageMapError::TooLarge { .. })
+        ));
+    }
+}
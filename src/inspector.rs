@@ -0,0 +1,191 @@
+use crate::prelude::*;
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedPawn>()
+            .add_systems(Startup, spawn_inspector)
+            .add_systems(
+                Update,
+                (
+                    select_pawn,
+                    clear_despawned_selection,
+                    highlight_selection,
+                    update_inspector,
+                ),
+            );
+    }
+}
+
+// Pawn shown in the inspector, picked by clicking in ClickMode::None
+#[derive(Resource, Default)]
+pub struct SelectedPawn(pub Option<Entity>);
+
+#[derive(Component)]
+struct Inspector;
+
+#[derive(Component)]
+struct InspectorText;
+
+const PICK_RADIUS: f32 = 0.6;
+
+fn spawn_inspector(mut commands: Commands) {
+    commands
+        .spawn((
+            panel(Style {
+                bottom: Val::Px(8.0),
+                right: Val::Px(8.0),
+                ..default()
+            }),
+            Inspector,
+        ))
+        .insert(Visibility::Hidden)
+        .with_children(|inspector| {
+            inspector.spawn((TextBundle::from_section("", text_style()), InspectorText));
+        });
+}
+
+fn select_pawn(
+    mut selected: ResMut<SelectedPawn>,
+    pawns: Query<(Entity, &Transform), With<Pawn>>,
+    cursor_position: Res<CursorPosition>,
+    mouse: Res<Input<MouseButton>>,
+    mode: Res<ClickMode>,
+) {
+    if *mode != ClickMode::None || !mouse.just_pressed(MouseButton::Left) || cursor_position.over_ui
+    {
+        return;
+    }
+
+    // Clicking empty ground clears the selection
+    selected.0 = pawns
+        .iter()
+        .map(|(entity, transform)| {
+            (
+                entity,
+                transform
+                    .translation
+                    .truncate()
+                    .distance(cursor_position.world_position),
+            )
+        })
+        .filter(|(_, distance)| *distance < PICK_RADIUS)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity);
+}
+
+fn clear_despawned_selection(mut selected: ResMut<SelectedPawn>, pawns: Query<(), With<Pawn>>) {
+    if let Some(entity) = selected.0 {
+        if !pawns.contains(entity) {
+            selected.0 = None;
+        }
+    }
+}
+
+fn highlight_selection(
+    mut gizmos: Gizmos,
+    selected: Res<SelectedPawn>,
+    pawns: Query<&Transform, With<Pawn>>,
+) {
+    if let Some(transform) = selected.0.and_then(|entity| pawns.get(entity).ok()) {
+        gizmos.circle_2d(transform.translation.truncate(), 0.6, Color::YELLOW);
+    }
+}
+
+fn trend(rate: f32) -> &'static str {
+    if rate > 0.1 {
+        "rising"
+    } else if rate < -0.1 {
+        "falling"
+    } else {
+        "steady"
+    }
+}
+
+fn update_inspector(
+    mut inspector: Query<&mut Visibility, With<Inspector>>,
+    mut text: Query<&mut Text, With<InspectorText>>,
+    // Needs the last time the simulation moved, to show which way they are heading
+    mut last: Local<Option<(Entity, u64, f32, f32)>>,
+    mut rates: Local<(f32, f32)>,
+    selected: Res<SelectedPawn>,
+    pawns: Query<(
+        &Name,
+        &Traits,
+        &Skills,
+        &Brain,
+        &Hunger,
+        &Recreation,
+        &Health,
+        &Mood,
+        &AiPath,
+        Option<&PathfindingTask>,
+    )>,
+    machines: Query<&GridLocation, With<Machine>>,
+    clock: Res<SimClock>,
+) {
+    let mut visibility = inspector.single_mut();
+    let (entity, (name, traits, skills, brain, hunger, recreation, health, mood, path, task)) =
+        match selected
+            .0
+            .and_then(|entity| Some((entity, pawns.get(entity).ok()?)))
+        {
+            Some(val) => val,
+            None => {
+                *visibility = Visibility::Hidden;
+                *last = None;
+                return;
+            }
+        };
+    *visibility = Visibility::Inherited;
+
+    match *last {
+        // Frames without a tick keep the previous trend
+        Some((last_entity, tick, _, _)) if last_entity == entity && tick == clock.tick() => {}
+        Some((last_entity, tick, last_hunger, last_recreation)) if last_entity == entity => {
+            let seconds = (clock.tick() - tick) as f32 * clock.delta_seconds();
+            *rates = (
+                (hunger.value - last_hunger) / seconds,
+                (recreation.value - last_recreation) / seconds,
+            );
+            *last = Some((entity, clock.tick(), hunger.value, recreation.value));
+        }
+        _ => {
+            *rates = (0.0, 0.0);
+            *last = Some((entity, clock.tick(), hunger.value, recreation.value));
+        }
+    }
+    let (hunger_rate, recreation_rate) = *rates;
+
+    let target = match brain.state() {
+        BrainState::OperateMachine(machine) => Some(*machine),
+        state => state.job_target(),
+    };
+    let target = match target {
+        Some(target) => match machines.get(target) {
+            Ok(location) => format!("{:?} at ({}, {})", target, location.x, location.y),
+            Err(_) => format!("{:?}", target),
+        },
+        None => "none".to_string(),
+    };
+
+    text.single_mut().sections[0].value = format!(
+        "{}\nTraits: {:?}\nConstruction: {} Cooking: {}\nState: {:?}\nTarget: {}\nHealth: {:.0}\nMood: {:.0} {:?}\nHunger: {:.0} ({})\nRecreation: {:.0} ({})\nPath: {} steps{}",
+        name,
+        traits.0,
+        skills.construction.level(),
+        skills.cooking.level(),
+        brain.state(),
+        target,
+        health.value,
+        mood.value,
+        mood.thoughts().collect::<Vec<_>>(),
+        hunger.value,
+        trend(hunger_rate),
+        recreation.value,
+        trend(recreation_rate),
+        path.locations.len(),
+        if task.is_some() { ", pathfinding" } else { "" }
+    );
+}
@@ -1,29 +1,67 @@
-#![allow(clippy::type_complexity)]
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+mod actions;
 mod ai;
 mod animation;
 mod app;
+mod ascii_map;
 mod buildings;
 mod camera;
+mod chunks;
+mod clock;
+mod debug;
+mod durability;
 mod graphics;
 mod grid;
+mod health;
+mod history;
+mod hud;
+mod identity;
+mod image_map;
+mod inspector;
+mod mapgen;
+mod mood;
 mod needs;
 mod pathfinding;
 mod player;
+mod population;
+mod replay;
+mod rng;
+mod scenario;
 mod utils;
+mod value_grid;
 
 pub mod prelude {
     pub use bevy::reflect::TypeUuid;
     pub use bevy::{prelude::*, utils::HashMap};
 
+    pub use crate::actions::*;
     pub use crate::ai::*;
     pub use crate::animation::*;
     pub use crate::app::*;
+    pub use crate::ascii_map::*;
     pub use crate::buildings::*;
     pub use crate::camera::*;
+    pub use crate::chunks::*;
+    pub use crate::clock::*;
+    pub use crate::debug::*;
+    pub use crate::durability::*;
     pub use crate::graphics::*;
     pub use crate::grid::*;
+    pub use crate::health::*;
+    pub use crate::history::*;
+    pub use crate::hud::*;
+    pub use crate::identity::*;
+    pub use crate::image_map::*;
+    pub use crate::inspector::*;
+    pub use crate::mapgen::*;
+    pub use crate::mood::*;
     pub use crate::needs::*;
     pub use crate::pathfinding::*;
     pub use crate::player::*;
+    pub use crate::population::*;
+    pub use crate::replay::*;
+    pub use crate::rng::*;
+    pub use crate::scenario::*;
     pub use crate::utils::*;
+    pub use crate::value_grid::*;
 }
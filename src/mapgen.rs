@@ -0,0 +1,390 @@
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapTile {
+    #[default]
+    Open,
+    Rock,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum MapAlgorithm {
+    // Random fill smoothed by a cellular automaton, fill is the starting rock chance
+    Caves {
+        fill: f32,
+        smoothing: u32,
+    },
+    // Solid rock with rectangular rooms carved out and joined by corridors
+    RoomsAndCorridors {
+        rooms: u32,
+        min_size: i32,
+        max_size: i32,
+    },
+    // Layered value noise, rock wherever it rises above threshold. Scale is in cells
+    RockField {
+        scale: f32,
+        threshold: f32,
+    },
+}
+
+// Part of a scenario, the seed comes from SimRng's MapGeneration stream
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct MapSettings {
+    pub algorithm: MapAlgorithm,
+    // Half width of the square kept open around the spawn point
+    pub spawn_radius: i32,
+    pub food_machines: u32,
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: MapAlgorithm::Caves {
+                fill: 0.45,
+                smoothing: 4,
+            },
+            spawn_radius: 5,
+            food_machines: 2,
+        }
+    }
+}
+
+pub struct GeneratedMap {
+    pub tiles: ValueGrid<MapTile>,
+    pub spawn: GridLocation,
+    // Food machines facing North, their use tile is directly below
+    pub machines: Vec<GridLocation>,
+}
+
+// Open pockets smaller than this are filled in rather than tunnelled to
+const MIN_REGION: usize = 16;
+
+pub fn spawn_point() -> GridLocation {
+    GridLocation::new(grid_size() as u32 / 2, grid_size() as u32 / 2)
+}
+
+// Every open cell ends up reachable from the spawn point, machines included
+pub fn generate_map<R: Rng + ?Sized>(settings: &MapSettings, rng: &mut R) -> GeneratedMap {
+    let mut tiles = match &settings.algorithm {
+        MapAlgorithm::Caves { fill, smoothing } => caves(*fill, *smoothing, rng),
+        MapAlgorithm::RoomsAndCorridors {
+            rooms,
+            min_size,
+            max_size,
+        } => rooms_and_corridors(*rooms, *min_size, *max_size, rng),
+        MapAlgorithm::RockField { scale, threshold } => rock_field(*scale, *threshold, rng),
+    };
+
+    let spawn = spawn_point();
+    // Wide enough for the machines to sit in a row with room to walk around them
+    let radius = settings
+        .spawn_radius
+        .max(4)
+        .max(settings.food_machines as i32 + 1);
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            if let Some(tile) = tiles.get_mut(&GridLocation(spawn.0 + IVec2::new(x, y))) {
+                *tile = MapTile::Open;
+            }
+        }
+    }
+
+    let machines = (0..settings.food_machines as i32)
+        .map(|i| {
+            let x = 2 * i - (settings.food_machines as i32 - 1);
+            GridLocation(spawn.0 + IVec2::new(x, 3))
+        })
+        .collect();
+
+    connect_regions(&mut tiles, &spawn);
+
+    GeneratedMap {
+        tiles,
+        spawn,
+        machines,
+    }
+}
+
+fn caves<R: Rng + ?Sized>(fill: f32, smoothing: u32, rng: &mut R) -> ValueGrid<MapTile> {
+    let mut tiles = ValueGrid::new(MapTile::Open);
+    for location in all_points() {
+        if rng.gen::<f32>() < fill {
+            tiles[&location] = MapTile::Rock;
+        }
+    }
+
+    for _ in 0..smoothing {
+        let previous = tiles.clone();
+        for location in all_points() {
+            // Outside the map counts as rock so caves close off at the edges
+            let rock = (-1..=1)
+                .flat_map(|x| (-1..=1).map(move |y| IVec2::new(x, y)))
+                .filter(|offset| *offset != IVec2::ZERO)
+                .filter(|offset| {
+                    !matches!(
+                        previous.get(&GridLocation(location.0 + *offset)),
+                        Some(MapTile::Open)
+                    )
+                })
+                .count();
+            tiles[&location] = match rock {
+                5.. => MapTile::Rock,
+                0..=3 => MapTile::Open,
+                _ => previous[&location],
+            };
+        }
+    }
+    tiles
+}
+
+fn rooms_and_corridors<R: Rng + ?Sized>(
+    rooms: u32,
+    min_size: i32,
+    max_size: i32,
+    rng: &mut R,
+) -> ValueGrid<MapTile> {
+    let mut tiles = ValueGrid::new(MapTile::Rock);
+    // Rooms keep a wall of rock on every side, so the largest fits the grid minus three
+    let largest = grid_size() as i32 - 3;
+    if largest < 1 {
+        return tiles;
+    }
+    let min_size = min_size.clamp(1, largest);
+    let max_size = max_size.clamp(min_size, largest);
+    let mut previous = spawn_point();
+    for _ in 0..rooms {
+        let size = IVec2::new(
+            rng.gen_range(min_size..=max_size),
+            rng.gen_range(min_size..=max_size),
+        );
+        let corner = IVec2::new(
+            rng.gen_range(1..grid_size() as i32 - size.x - 1),
+            rng.gen_range(1..grid_size() as i32 - size.y - 1),
+        );
+        for x in 0..size.x {
+            for y in 0..size.y {
+                tiles[&GridLocation(corner + IVec2::new(x, y))] = MapTile::Open;
+            }
+        }
+
+        let center = GridLocation(corner + size / 2);
+        carve_corridor(&mut tiles, &previous, &center, |_| false);
+        previous = center;
+    }
+    tiles
+}
+
+// Smoothly interpolated random lattice, summed over a few octaves
+fn rock_field<R: Rng + ?Sized>(scale: f32, threshold: f32, rng: &mut R) -> ValueGrid<MapTile> {
+    const OCTAVES: u32 = 3;
+    let mut noise = ValueGrid::new(0.0f32);
+    let mut total_amplitude = 0.0;
+    for octave in 0..OCTAVES {
+        let spacing = (scale / 2.0f32.powi(octave as i32)).max(1.0);
+        let amplitude = 0.5f32.powi(octave as i32);
+        total_amplitude += amplitude;
+
+        let lattice_size = (grid_size() as f32 / spacing) as usize + 2;
+        let lattice = (0..lattice_size * lattice_size)
+            .map(|_| rng.gen::<f32>())
+            .collect::<Vec<_>>();
+        let corner = |x: usize, y: usize| lattice[x * lattice_size + y];
+
+        for location in all_points() {
+            let position = location.as_vec2() / spacing;
+            let (x, y) = (position.x as usize, position.y as usize);
+            let fraction = position.fract();
+            let smooth = fraction * fraction * (Vec2::splat(3.0) - 2.0 * fraction);
+            let bottom = corner(x, y) + (corner(x + 1, y) - corner(x, y)) * smooth.x;
+            let top = corner(x, y + 1) + (corner(x + 1, y + 1) - corner(x, y + 1)) * smooth.x;
+            noise[&location] += (bottom + (top - bottom) * smooth.y) * amplitude;
+        }
+    }
+
+    ValueGrid::from_fn(|location| {
+        if noise[location] / total_amplitude > threshold {
+            MapTile::Rock
+        } else {
+            MapTile::Open
+        }
+    })
+}
+
+// L shaped tunnel, stops early once stop says it reached somewhere already connected
+fn carve_corridor(
+    tiles: &mut ValueGrid<MapTile>,
+    start: &GridLocation,
+    end: &GridLocation,
+    stop: impl Fn(&GridLocation) -> bool,
+) -> Vec<GridLocation> {
+    let mut carved = Vec::new();
+    let mut current = start.0;
+    while current != end.0 {
+        if current.x != end.x {
+            current.x += (end.x - current.x).signum();
+        } else {
+            current.y += (end.y - current.y).signum();
+        }
+        let location = GridLocation(current);
+        if stop(&location) {
+            break;
+        }
+        tiles[&location] = MapTile::Open;
+        carved.push(location);
+    }
+    carved
+}
+
+// Open regions in a stable order, each a list of cells
+fn open_regions(tiles: &ValueGrid<MapTile>) -> Vec<Vec<GridLocation>> {
+    let mut visited = ValueGrid::new(false);
+    let mut regions = Vec::new();
+    for start in all_points() {
+        if visited[&start] || tiles[&start] == MapTile::Rock {
+            continue;
+        }
+        visited[&start] = true;
+        let mut region = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(cell) = queue.pop_front() {
+            for (neighbor, tile) in tiles.neighbors(&cell) {
+                if *tile == MapTile::Open && !visited[&neighbor] {
+                    visited[&neighbor] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+            region.push(cell);
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+// Small pockets are filled, bigger ones get a tunnel towards the spawn
+fn connect_regions(tiles: &mut ValueGrid<MapTile>, spawn: &GridLocation) {
+    // Filled before any tunnel is carved, one passing through a pocket would be cut by it
+    let mut regions = open_regions(tiles);
+    regions.retain(|region| {
+        if region.len() >= MIN_REGION || region.contains(spawn) {
+            return true;
+        }
+        for cell in region {
+            tiles[cell] = MapTile::Rock;
+        }
+        false
+    });
+    let mut region_of = ValueGrid::new(None);
+    for (index, region) in regions.iter().enumerate() {
+        for cell in region {
+            region_of[cell] = Some(index);
+        }
+    }
+
+    let mut connected = ValueGrid::new(false);
+    let mut joined = vec![false; regions.len()];
+    let mut join = |index: usize, connected: &mut ValueGrid<bool>| {
+        if !joined[index] {
+            joined[index] = true;
+            for cell in &regions[index] {
+                connected[cell] = true;
+            }
+        }
+    };
+    if let Some(main) = region_of[spawn] {
+        join(main, &mut connected);
+    }
+
+    for index in 0..regions.len() {
+        if connected[&regions[index][0]] {
+            continue;
+        }
+        let carved = carve_corridor(tiles, &regions[index][0], spawn, |cell| connected[cell]);
+        join(index, &mut connected);
+        // Regions the tunnel passed through are reached through it too
+        for cell in &carved {
+            connected[cell] = true;
+            if let Some(crossed) = region_of[cell] {
+                join(crossed, &mut connected);
+            }
+        }
+    }
+}
+
+impl GeneratedMap {
+    pub fn spawn(&self, commands: &mut Commands) {
+        for (location, tile) in self.tiles.iter() {
+            if *tile == MapTile::Rock {
+                spawn_wall(commands, location);
+            }
+        }
+        for location in &self.machines {
+            Blueprint::FoodMachine {
+                definition: BuildingDefinition::food_machine(),
+                location: location.clone(),
+                rotation: Rotation::North,
+                rate: 10.0,
+            }
+            .spawn(commands);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn every_algorithm_connects_spawn_and_machines() {
+        let algorithms = [
+            MapSettings::default().algorithm,
+            MapAlgorithm::RoomsAndCorridors {
+                rooms: 12,
+                min_size: 4,
+                max_size: 12,
+            },
+            // Sizes past the grid are clamped to it
+            MapAlgorithm::RoomsAndCorridors {
+                rooms: 2,
+                min_size: 500,
+                max_size: 1000,
+            },
+            MapAlgorithm::RockField {
+                scale: 16.0,
+                threshold: 0.55,
+            },
+        ];
+        for algorithm in algorithms {
+            let settings = MapSettings {
+                algorithm,
+                ..default()
+            };
+            // 27 and 36 once cut a tunnel by filling a pocket it passed through
+            for seed in (0..8).chain([27, 36]) {
+                let map = generate_map(&settings, &mut ChaCha8Rng::seed_from_u64(seed));
+                let regions = open_regions(&map.tiles);
+                assert_eq!(
+                    regions.len(),
+                    1,
+                    "{:?} with seed {} left {:?} open regions",
+                    settings.algorithm,
+                    seed,
+                    regions.iter().map(Vec::len).collect::<Vec<_>>()
+                );
+
+                for machine in &map.machines {
+                    assert_eq!(map.tiles[machine], MapTile::Open);
+                    let use_tile = GridLocation(machine.0 - IVec2::Y);
+                    assert!(regions[0].contains(&use_tile));
+                }
+                assert!(regions[0].contains(&map.spawn));
+            }
+        }
+    }
+}
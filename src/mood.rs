@@ -0,0 +1,193 @@
+use crate::prelude::*;
+
+pub struct MoodPlugin;
+
+impl Plugin for MoodPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            SimulationTick,
+            (notice_deaths.after(kill_pawns), judge_rooms, update_mood).chain(),
+        );
+    }
+}
+
+// Mood drifts towards what needs and thoughts say at this many points per second
+const MOOD_CHANGE: f32 = 5.0;
+// Deaths closer than this, in cells, are seen
+const SIGHT: f32 = 8.0;
+// Open areas smaller than this feel cramped
+const CRAMPED_CELLS: usize = 36;
+
+// Short lived reasons for a pawn to feel better or worse
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Thought {
+    AteRecently,
+    AteFineMeal,
+    SawDeath,
+    // Someone in the colony died, whether or not it was seen
+    Grieving,
+    Cramped,
+    // Just had a mental break, keeps the next one from following straight away
+    Catharsis,
+}
+
+impl Thought {
+    pub fn effect(&self) -> f32 {
+        match self {
+            Thought::AteRecently => 15.0,
+            Thought::AteFineMeal => 25.0,
+            Thought::SawDeath => -25.0,
+            Thought::Grieving => -10.0,
+            Thought::Cramped => -10.0,
+            Thought::Catharsis => 30.0,
+        }
+    }
+
+    // Seconds before the thought fades, thinking it again starts over
+    fn duration(&self) -> f32 {
+        match self {
+            Thought::AteRecently | Thought::AteFineMeal => 120.0,
+            Thought::SawDeath => 180.0,
+            Thought::Grieving => 240.0,
+            Thought::Cramped => 5.0,
+            Thought::Catharsis => 90.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum MoodLevel {
+    // Breaks down as soon as it can
+    Breaking,
+    // Won't take structure jobs
+    Miserable,
+    // Works at half speed
+    Low,
+    Content,
+}
+
+#[derive(Component, Debug)]
+pub struct Mood {
+    pub value: f32,
+    thoughts: Vec<(Thought, f32)>,
+}
+
+impl Default for Mood {
+    fn default() -> Self {
+        Self {
+            value: 60.0,
+            thoughts: Vec::new(),
+        }
+    }
+}
+
+impl Mood {
+    pub fn think(&mut self, thought: Thought) {
+        match self
+            .thoughts
+            .iter_mut()
+            .find(|(current, _)| *current == thought)
+        {
+            Some((_, remaining)) => *remaining = thought.duration(),
+            None => self.thoughts.push((thought, thought.duration())),
+        }
+    }
+
+    pub fn thoughts(&self) -> impl Iterator<Item = Thought> + '_ {
+        self.thoughts.iter().map(|(thought, _)| *thought)
+    }
+
+    // Needs set the baseline, hunger weighing more than recreation
+    pub fn target(&self, hunger: f32, recreation: f32) -> f32 {
+        let needs = hunger * 0.6 + recreation * 0.4;
+        let thoughts = self.thoughts().map(|thought| thought.effect()).sum::<f32>();
+        (needs + thoughts).clamp(0.0, 100.0)
+    }
+
+    pub fn level(&self) -> MoodLevel {
+        match self.value {
+            value if value < 10.0 => MoodLevel::Breaking,
+            value if value < 20.0 => MoodLevel::Miserable,
+            value if value < 30.0 => MoodLevel::Low,
+            _ => MoodLevel::Content,
+        }
+    }
+
+    pub fn work_speed(&self) -> f32 {
+        match self.level() {
+            MoodLevel::Low => 0.5,
+            MoodLevel::Content => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    pub fn refuses_work(&self) -> bool {
+        self.level() <= MoodLevel::Miserable
+    }
+}
+
+fn notice_deaths(
+    mut deaths: EventReader<DeathEvent>,
+    mut pawns: Query<(Entity, &Transform, &mut Mood)>,
+) {
+    for death in deaths.iter() {
+        for (pawn, transform, mut mood) in &mut pawns {
+            if pawn != death.pawn
+                && transform.translation.truncate().distance(death.position) < SIGHT
+            {
+                mood.think(Thought::SawDeath);
+            }
+        }
+    }
+}
+
+fn judge_rooms(
+    mut pawns: Query<(&Transform, &mut Mood)>,
+    components: Res<ConnectedComponents<Structure>>,
+) {
+    for (transform, mut mood) in &mut pawns {
+        let size = GridLocation::from_world(transform.translation.truncate())
+            .and_then(|location| components.component_size(&location));
+        if matches!(size, Some(size) if size < CRAMPED_CELLS) {
+            mood.think(Thought::Cramped);
+        }
+    }
+}
+
+fn update_mood(mut pawns: Query<(&mut Mood, &Hunger, &Recreation)>, clock: Res<SimClock>) {
+    let delta = clock.delta_seconds();
+    for (mut mood, hunger, recreation) in &mut pawns {
+        mood.thoughts.retain_mut(|(_, remaining)| {
+            *remaining -= delta;
+            *remaining > 0.0
+        });
+        let target = mood.target(hunger.value, recreation.value);
+        let change = (target - mood.value).clamp(-MOOD_CHANGE * delta, MOOD_CHANGE * delta);
+        mood.value += change;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thoughts_shift_mood_and_refresh() {
+        let mut mood = Mood::default();
+        mood.think(Thought::AteRecently);
+        assert_eq!(mood.target(0.0, 0.0), 15.0);
+        assert_eq!(mood.target(50.0, 100.0), 85.0);
+
+        mood.think(Thought::SawDeath);
+        mood.think(Thought::SawDeath);
+        assert_eq!(mood.thoughts().count(), 2);
+        assert_eq!(mood.target(0.0, 0.0), 0.0);
+        assert_eq!(mood.target(80.0, 80.0), 70.0);
+
+        mood.value = 5.0;
+        assert_eq!(mood.level(), MoodLevel::Breaking);
+        assert!(mood.refuses_work());
+        mood.value = 25.0;
+        assert_eq!(mood.work_speed(), 0.5);
+    }
+}
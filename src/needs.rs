@@ -4,7 +4,7 @@ pub struct NeedsPlugin;
 
 impl Plugin for NeedsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (apply_hunger, apply_recreation));
+        app.add_systems(SimulationTick, (apply_hunger, apply_recreation));
     }
 }
 
@@ -19,15 +19,29 @@ pub struct Recreation {
     pub value: f32,
 }
 
-// Could be generic needs system
-fn apply_hunger(mut hungers: Query<&mut Hunger>, time: Res<Time>) {
-    for mut hunger in &mut hungers {
-        hunger.value -= time.delta_seconds() * 3.0;
+// Pawns with nothing to do stop to relax below this, until they're full again
+pub const RELAX_BELOW: f32 = 30.0;
+const RELAX_RATE: f32 = 30.0;
+
+// Could be generic needs system. Needs bottom out at zero, HealthPlugin hurts pawns left there
+fn apply_hunger(mut hungers: Query<(&mut Hunger, &Traits)>, clock: Res<SimClock>) {
+    for (mut hunger, traits) in &mut hungers {
+        hunger.value = (hunger.value - clock.delta_seconds() * 3.0 * traits.hunger_rate()).max(0.0);
     }
 }
 
-fn apply_recreation(mut recreations: Query<&mut Recreation>, time: Res<Time>) {
-    for mut recreations in &mut recreations {
-        recreations.value -= time.delta_seconds() * 10.0;
+// Relaxing pawns refill it instead
+fn apply_recreation(
+    mut recreations: Query<(&mut Recreation, &Traits, &Brain)>,
+    clock: Res<SimClock>,
+) {
+    for (mut recreations, traits, brain) in &mut recreations {
+        recreations.value = match brain.state() {
+            BrainState::Relax => {
+                (recreations.value + clock.delta_seconds() * RELAX_RATE).min(100.0)
+            }
+            _ => (recreations.value - clock.delta_seconds() * 10.0 * traits.recreation_rate())
+                .max(0.0),
+        };
     }
 }
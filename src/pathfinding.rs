@@ -5,13 +5,13 @@ use bevy::tasks::{AsyncComputeTaskPool, Task};
 use futures_lite::future;
 use pathfinding::prelude::astar;
 
-use crate::grid::{Grid, GridLocation, GRID_SIZE};
+use crate::grid::{Grid, GridLocation};
 
 pub struct PathfindingPlugin;
 
 impl Plugin for PathfindingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_pathfinding_to_ai);
+        app.add_systems(TickBoundary, apply_pathfinding_to_ai.after(GridSync));
     }
 }
 
@@ -21,36 +21,11 @@ pub struct AiPath {
 }
 
 pub fn neumann_neighbors<T>(grid: &Grid<T>, location: &GridLocation) -> Vec<GridLocation> {
-    let (x, y) = (location.x as u32, location.y as u32);
-
-    let mut sucessors = Vec::new();
-    if let Some(left) = x.checked_sub(1) {
-        let location = GridLocation::new(left, y);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    if let Some(down) = y.checked_sub(1) {
-        let location = GridLocation::new(x, down);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    if x + 1 < GRID_SIZE as u32 {
-        let right = x + 1;
-        let location = GridLocation::new(right, y);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    if y + 1 < GRID_SIZE as u32 {
-        let up = y + 1;
-        let location = GridLocation::new(x, up);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    sucessors
+    location
+        .neighbors()
+        .into_iter()
+        .filter(|neighbor| !grid.occupied(neighbor))
+        .collect()
 }
 
 pub struct Path {
@@ -107,10 +82,16 @@ impl<T> Grid<T> {
 }
 
 #[derive(Component)]
-pub struct PathfindingTask(Task<Result<Path, PathfindingError>>);
+pub struct PathfindingTask {
+    task: Task<Result<Path, PathfindingError>>,
+    // Grid generation the path was searched on, see apply_pathfinding_to_ai
+    generation: u64,
+    due: u64,
+}
 
 pub fn spawn_optimized_pathfinding_task<T: Component>(
     commands: &mut Commands,
+    clock: &SimClock,
     target: Entity,
     grid: &Grid<T>,
     start: GridLocation,
@@ -126,6 +107,7 @@ pub fn spawn_optimized_pathfinding_task<T: Component>(
     // Must clone because the grid can change between frames
     // Must box to prevent stack overflows on very large grids
     let grid = Box::new(grid.clone());
+    let generation = grid.entities.current_generation();
 
     let task = thread_pool.spawn(async move {
         let mut path = grid.path_to(&start, &end);
@@ -133,26 +115,44 @@ pub fn spawn_optimized_pathfinding_task<T: Component>(
         path
     });
 
-    commands.entity(target).insert(PathfindingTask(task));
+    commands.entity(target).insert(PathfindingTask {
+        task,
+        generation,
+        due: clock.deadline(),
+    });
 }
 
+// Paths arrive on the tick they were due however long the search took. Paths through chunks
+// written since the search started are dropped, the pawn asks again once its path is empty
 pub fn apply_pathfinding_to_ai(
     mut commands: Commands,
     mut paths: Query<&mut AiPath>,
     mut tasks: Query<(Entity, &mut PathfindingTask)>,
+    structures: Res<Grid<Structure>>,
+    clock: Res<SimClock>,
 ) {
     for (task_entity, mut task) in &mut tasks {
-        if let Some(result) = future::block_on(future::poll_once(&mut task.0)) {
-            commands.entity(task_entity).remove::<PathfindingTask>();
-
-            if let Ok(mut ai_path) = paths.get_mut(task_entity) {
-                if let Ok(path) = result {
-                    ai_path.locations.clear();
-                    for location in path.steps.iter() {
-                        ai_path
-                            .locations
-                            .push_back(Vec2::new(location.x as f32, location.y as f32));
-                    }
+        if task.due > clock.tick() {
+            continue;
+        }
+        let result = future::block_on(&mut task.task);
+        commands.entity(task_entity).remove::<PathfindingTask>();
+        let searched = task.generation;
+
+        if let Ok(mut ai_path) = paths.get_mut(task_entity) {
+            if let Ok(path) = result {
+                if path
+                    .steps
+                    .iter()
+                    .any(|step| structures.entities.generation(&step.chunk()) > searched)
+                {
+                    continue;
+                }
+                ai_path.locations.clear();
+                for location in path.steps.iter() {
+                    ai_path
+                        .locations
+                        .push_back(Vec2::new(location.x as f32, location.y as f32));
                 }
             }
         }
@@ -165,18 +165,21 @@ pub struct PathfindingError;
 #[cfg(test)]
 mod tests {
 
-    use bevy::prelude::Entity;
-
-    use crate::grid::{Grid, GridLocation};
+    use crate::{ascii_map::AsciiMap, grid::GridLocation};
 
     #[test]
     fn basic_pathfinding() {
         let goal = GridLocation::new(4, 6);
         let start = GridLocation::new(1, 1);
-        let mut grid: Grid<()> = Grid::default();
-        grid.entities[2][0] = Some(Entity::from_raw(0));
-        grid.entities[2][1] = Some(Entity::from_raw(0));
-        grid.entities[2][2] = Some(Entity::from_raw(0));
+        let map = AsciiMap::parse(
+            "
+            ..#
+            ..#
+            ..#
+            ",
+        )
+        .unwrap();
+        let grid = map.grid::<()>();
 
         let result = grid.path_to(&start, &goal);
         assert!(result.is_ok());
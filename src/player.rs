@@ -1,24 +1,201 @@
 use crate::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ClickMode>().add_systems(
-            Update,
-            (left_click_to_build, right_click_to_remove, set_build_mode),
-        );
+        app.init_resource::<ClickMode>()
+            .init_resource::<HeldBuilding>()
+            .init_resource::<BuildRotation>()
+            .init_resource::<DesignateDrag>()
+            .init_resource::<BuildDrag>()
+            .add_systems(Startup, spawn_build_readout)
+            .add_systems(PostUpdate, update_ghost)
+            .add_systems(
+                Update,
+                (
+                    drag_to_build,
+                    right_click_to_deconstruct,
+                    drag_to_move,
+                    set_build_mode,
+                    rotate_building,
+                    cycle_drag_shape,
+                    draw_build_preview,
+                    draw_designate_preview,
+                    update_build_readout,
+                ),
+            );
     }
 }
 
-#[derive(Default, Resource)]
-enum ClickMode {
+#[derive(Default, Resource, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ClickMode {
     None,
     BuildWall,
     #[default]
     BuildFoodMachine,
+    BuildKitchen,
+    BuildBed,
+    Move,
+}
+
+// How a build drag fills the cells between where it started and the cursor, T to cycle
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DragShape {
+    #[default]
+    Line,
+    Room,
+    Area,
+}
+
+impl DragShape {
+    pub fn next(&self) -> Self {
+        match self {
+            DragShape::Line => DragShape::Room,
+            DragShape::Room => DragShape::Area,
+            DragShape::Area => DragShape::Line,
+        }
+    }
+
+    // Lines follow whichever axis moved the most, rooms are the outline of the rectangle and areas fill it
+    pub fn cells(&self, start: &GridLocation, end: &GridLocation) -> Vec<GridLocation> {
+        let (min, max) = (start.0.min(end.0), start.0.max(end.0));
+        let rectangle =
+            (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)));
+        match self {
+            DragShape::Line => {
+                let delta = (end.0 - start.0).abs();
+                rectangle
+                    .filter(|cell| {
+                        if delta.x >= delta.y {
+                            cell.y == start.y
+                        } else {
+                            cell.x == start.x
+                        }
+                    })
+                    .map(GridLocation)
+                    .collect()
+            }
+            DragShape::Room => rectangle
+                .filter(|cell| {
+                    cell.x == min.x || cell.x == max.x || cell.y == min.y || cell.y == max.y
+                })
+                .map(GridLocation)
+                .collect(),
+            DragShape::Area => rectangle.map(GridLocation).collect(),
+        }
+    }
+}
+
+#[derive(Default, Resource)]
+struct BuildDrag {
+    start: Option<GridLocation>,
+    end: Option<GridLocation>,
+    shape: DragShape,
+}
+
+impl BuildDrag {
+    fn cells(&self) -> Vec<GridLocation> {
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) => self.shape.cells(start, end),
+            _ => Vec::new(),
+        }
+    }
+
+    fn blueprints(
+        &self,
+        mode: &ClickMode,
+        rotation: Rotation,
+        layers: &TileLayers,
+    ) -> Vec<Blueprint> {
+        mode.blueprints(self.cells(), rotation, layers)
+    }
+}
+
+#[derive(Component)]
+struct BuildReadout;
+
+// Translucent sprite showing what a click would place, respawned when the blueprint or its check changes
+#[derive(Component)]
+struct Ghost;
+
+type GhostKey = (ClickMode, Rotation, GridLocation, PlacementKey);
+
+impl ClickMode {
+    pub fn builds(&self) -> bool {
+        !matches!(self, ClickMode::None | ClickMode::Move)
+    }
+
+    // Plural name for the build readout
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClickMode::None | ClickMode::Move => "",
+            ClickMode::BuildWall => "walls",
+            ClickMode::BuildFoodMachine => "food machines",
+            ClickMode::BuildKitchen => "kitchens",
+            ClickMode::BuildBed => "beds",
+        }
+    }
+
+    // One blueprint anchored on each cell that fits, buildings overlapping an earlier one or its
+    // use tiles are skipped
+    pub fn blueprints(
+        &self,
+        cells: Vec<GridLocation>,
+        rotation: Rotation,
+        layers: &TileLayers,
+    ) -> Vec<Blueprint> {
+        let mut claimed = Vec::new();
+        let mut blueprints = Vec::new();
+        for cell in cells {
+            let blueprint = match self.blueprint(cell, rotation) {
+                Some(blueprint) => blueprint,
+                None => continue,
+            };
+            let cells = blueprint.cells();
+            let food_tiles = blueprint.food_tiles();
+            if !blueprint.can_place(layers)
+                || cells
+                    .iter()
+                    .chain(&food_tiles)
+                    .any(|cell| claimed.contains(cell))
+            {
+                continue;
+            }
+            claimed.extend(cells);
+            claimed.extend(food_tiles);
+            blueprints.push(blueprint);
+        }
+        blueprints
+    }
+
+    // What a click at this location would place, only ghosted before a drag starts
+    pub fn blueprint(&self, location: GridLocation, rotation: Rotation) -> Option<Blueprint> {
+        let (definition, rate) = match self {
+            ClickMode::None | ClickMode::Move => return None,
+            ClickMode::BuildWall => return Some(Blueprint::Wall { location }),
+            ClickMode::BuildBed => return Some(Blueprint::Bed { location, rotation }),
+            ClickMode::BuildFoodMachine => (BuildingDefinition::food_machine(), 10.0),
+            ClickMode::BuildKitchen => (BuildingDefinition::kitchen(), 20.0),
+        };
+        Some(Blueprint::FoodMachine {
+            definition,
+            location,
+            rotation,
+            rate,
+        })
+    }
 }
 
+// Rotation applied to the next placed building, R to turn it
+#[derive(Default, Resource)]
+struct BuildRotation(Rotation);
+
+// Cell a building was grabbed by in ClickMode::Move, dropped when the mouse is released
+#[derive(Default, Resource)]
+struct HeldBuilding(Option<GridLocation>);
+
 fn set_build_mode(keyboard: Res<Input<KeyCode>>, mut mode: ResMut<ClickMode>) {
     if keyboard.just_pressed(KeyCode::Key1) {
         *mode = ClickMode::None;
@@ -29,63 +206,323 @@ fn set_build_mode(keyboard: Res<Input<KeyCode>>, mut mode: ResMut<ClickMode>) {
     if keyboard.just_pressed(KeyCode::Key3) {
         *mode = ClickMode::BuildFoodMachine;
     }
+    if keyboard.just_pressed(KeyCode::Key4) {
+        *mode = ClickMode::Move;
+    }
+    if keyboard.just_pressed(KeyCode::Key5) {
+        *mode = ClickMode::BuildKitchen;
+    }
+    if keyboard.just_pressed(KeyCode::Key6) {
+        *mode = ClickMode::BuildBed;
+    }
 }
 
-fn left_click_to_build(
-    mut commands: Commands,
-    wall_grid: Res<Grid<Wall>>,
+fn cycle_drag_shape(keyboard: Res<Input<KeyCode>>, mut drag: ResMut<BuildDrag>) {
+    if keyboard.just_pressed(KeyCode::T) {
+        drag.shape = drag.shape.next();
+    }
+}
+
+// Buildings are placed as one batch on release, and only if the whole batch is affordable.
+// A click without dragging places a single one
+// Built on the next tick, where it is checked again against whatever changed in between
+fn drag_to_build(
+    mut drag: ResMut<BuildDrag>,
+    mut actions: ResMut<PlayerActions>,
     cursor_position: Res<CursorPosition>,
     mouse: Res<Input<MouseButton>>,
     mode: Res<ClickMode>,
+    rotation: Res<BuildRotation>,
 ) {
-    if !mouse.pressed(MouseButton::Left) {
+    if !mode.builds() {
+        drag.start = None;
         return;
     }
 
     if let Some(location) = GridLocation::from_world(cursor_position.world_position) {
-        if wall_grid.occupied(&location) {
-            return;
+        if mouse.just_pressed(MouseButton::Left) && !cursor_position.over_ui {
+            drag.start = Some(location.clone());
         }
-        match mode.as_ref() {
-            ClickMode::None => {}
-            ClickMode::BuildWall => {
-                commands.spawn((
-                    SpatialBundle::default(),
-                    Wall { _health: 10.0 },
-                    LockToGrid,
-                    WallSprite::None,
-                    location,
-                ));
+        drag.end = Some(location);
+    }
+
+    if mouse.just_released(MouseButton::Left) && drag.start.is_some() {
+        actions.push(PlayerAction::Build {
+            mode: *mode,
+            rotation: rotation.0,
+            cells: drag.cells(),
+        });
+        drag.start = None;
+    }
+}
+
+fn draw_build_preview(
+    mut gizmos: Gizmos,
+    drag: Res<BuildDrag>,
+    placement: PlacementCheck,
+    mode: Res<ClickMode>,
+    rotation: Res<BuildRotation>,
+) {
+    if drag.start.is_none() {
+        return;
+    }
+    let blueprints = drag.blueprints(&mode, rotation.0, &placement.layers);
+    // A trapping batch is rejected as a whole
+    let allowed = placement.allows_batch(&blueprints);
+    let placed = blueprints
+        .iter()
+        .flat_map(|blueprint| blueprint.cells())
+        .collect::<Vec<_>>();
+    for cell in drag.cells().iter().chain(&placed) {
+        let color = if allowed && placed.contains(cell) {
+            Color::GREEN
+        } else {
+            Color::RED
+        };
+        gizmos.rect_2d(cell.as_vec2(), 0.0, Vec2::splat(0.9), color);
+    }
+    for tile in blueprints
+        .iter()
+        .flat_map(|blueprint| blueprint.food_tiles())
+    {
+        gizmos.rect_2d(tile.as_vec2(), 0.0, Vec2::splat(0.6), Color::GREEN);
+    }
+}
+
+fn spawn_build_readout(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        }),
+        BuildReadout,
+    ));
+}
+
+fn update_build_readout(
+    mut readout: Query<(&mut Text, &mut Style, &mut Visibility), With<BuildReadout>>,
+    drag: Res<BuildDrag>,
+    layers: TileLayers,
+    stockpile: Res<Stockpile>,
+    cursor_position: Res<CursorPosition>,
+    mode: Res<ClickMode>,
+    rotation: Res<BuildRotation>,
+) {
+    let (mut text, mut style, mut visibility) = readout.single_mut();
+    if drag.start.is_none() {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+
+    let blueprints = drag.blueprints(&mode, rotation.0, &layers);
+    let cost = blueprints
+        .iter()
+        .map(|blueprint| blueprint.cost())
+        .sum::<u32>();
+
+    *visibility = Visibility::Visible;
+    style.left = Val::Px(cursor_position.screen_position.x + 16.0);
+    style.top = Val::Px(cursor_position.screen_position.y + 16.0);
+    text.sections[0].value = format!("{} {}, {} materials", blueprints.len(), mode.label(), cost);
+    text.sections[0].style.color = if cost > stockpile.materials {
+        Color::RED
+    } else {
+        Color::WHITE
+    };
+}
+
+fn update_ghost(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    ghosts: Query<Entity, With<Ghost>>,
+    atlas: Res<CharacterAtlas>,
+    placement: PlacementCheck,
+    drag: Res<BuildDrag>,
+    cursor_position: Res<CursorPosition>,
+    mode: Res<ClickMode>,
+    rotation: Res<BuildRotation>,
+    mut shown: Local<Option<(GhostKey, Vec<GridLocation>, Color)>>,
+) {
+    let location = GridLocation::from_world(cursor_position.world_position)
+        .filter(|_| drag.start.is_none() && !cursor_position.over_ui);
+    let (location, blueprint) = match location
+        .and_then(|location| Some((location.clone(), mode.blueprint(location, rotation.0)?)))
+    {
+        Some(found) => found,
+        None => {
+            if shown.take().is_some() {
+                for ghost in &ghosts {
+                    commands.entity(ghost).despawn();
+                }
             }
-            ClickMode::BuildFoodMachine => {
-                commands.spawn((
-                    SpatialBundle::default(),
-                    Machine {
-                        use_offset: IVec2 { x: 0, y: -1 },
-                    },
-                    FoodMachine { rate: 10.0 },
-                    LockToGrid,
-                    MachineSprite::FoodMachine,
-                    Wall { _health: 10.0 },
-                    location,
-                ));
+            return;
+        }
+    };
+
+    // The trap check floods the map, so it only runs again when something it reads changed
+    let key = (*mode, rotation.0, location, placement.key());
+    if let Some((shown_key, food_tiles, indicator)) = shown.as_ref() {
+        if *shown_key == key {
+            for tile in food_tiles {
+                gizmos.rect_2d(tile.as_vec2(), 0.0, Vec2::splat(0.6), *indicator);
             }
+            return;
         }
     }
+    for ghost in &ghosts {
+        commands.entity(ghost).despawn();
+    }
+
+    let (tint, indicator) = if placement.allows(&blueprint) {
+        (Color::rgba(0.4, 1.0, 0.4, 0.5), Color::GREEN)
+    } else {
+        (Color::rgba(1.0, 0.4, 0.4, 0.5), Color::RED)
+    };
+    let (index, angle) = match &blueprint {
+        Blueprint::Wall { .. } => (WallSprite::None.index(), 0.0),
+        Blueprint::FoodMachine {
+            definition,
+            rotation,
+            ..
+        } => (definition.sprite.index(), rotation.angle()),
+        Blueprint::Bed { rotation, .. } => (MachineSprite::Bed.index(), rotation.angle()),
+    };
+    for cell in blueprint.cells() {
+        commands.spawn((
+            SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index,
+                    color: tint,
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                texture_atlas: atlas.clone(),
+                // Above structures, below pawns
+                transform: Transform::from_translation(cell.as_vec2().extend(500.0))
+                    .with_rotation(Quat::from_rotation_z(angle)),
+                ..default()
+            },
+            Ghost,
+        ));
+    }
+    // Where pawns will stand to use the machine
+    let food_tiles = blueprint.food_tiles();
+    for tile in &food_tiles {
+        gizmos.rect_2d(tile.as_vec2(), 0.0, Vec2::splat(0.6), indicator);
+    }
+    *shown = Some((key, food_tiles, indicator));
 }
 
-fn right_click_to_remove(
-    mut commands: Commands,
-    wall_grid: Res<Grid<Wall>>,
+fn rotate_building(keyboard: Res<Input<KeyCode>>, mut rotation: ResMut<BuildRotation>) {
+    if keyboard.just_pressed(KeyCode::R) {
+        rotation.0 = rotation.0.next();
+    }
+}
+
+// Whether the current right click drag designates or cancels, decided by the first cell clicked,
+// and the cells it passed over so the whole drag is applied and undone together
+#[derive(Default, Resource)]
+struct DesignateDrag {
+    designate: Option<bool>,
+    cells: Vec<GridLocation>,
+}
+
+fn right_click_to_deconstruct(
+    mut drag: ResMut<DesignateDrag>,
+    mut actions: ResMut<PlayerActions>,
+    layers: TileLayers,
+    designated: Query<(), With<Deconstruct>>,
     cursor_position: Res<CursorPosition>,
     mouse: Res<Input<MouseButton>>,
 ) {
     if !mouse.pressed(MouseButton::Right) {
+        if let Some(designate) = drag.designate.take() {
+            let cells = std::mem::take(&mut drag.cells);
+            actions.push(PlayerAction::Designate { cells, designate });
+        }
+        return;
+    }
+    if cursor_position.over_ui {
         return;
     }
     if let Some(location) = GridLocation::from_world(cursor_position.world_position) {
-        if let Some(entity) = wall_grid[&location] {
-            commands.entity(entity).despawn_recursive();
+        if let Some(entity) = layers.get(TileLayer::Structure, &location) {
+            drag.designate.get_or_insert(!designated.contains(entity));
+            if !drag.cells.contains(&location) {
+                drag.cells.push(location);
+            }
         }
     }
 }
+
+fn draw_designate_preview(mut gizmos: Gizmos, drag: Res<DesignateDrag>) {
+    let color = match drag.designate {
+        Some(true) => Color::ORANGE,
+        Some(false) => Color::GRAY,
+        None => return,
+    };
+    for cell in &drag.cells {
+        gizmos.rect_2d(cell.as_vec2(), 0.0, Vec2::splat(0.9), color);
+    }
+}
+
+fn drag_to_move(
+    mut held: ResMut<HeldBuilding>,
+    mut actions: ResMut<PlayerActions>,
+    layers: TileLayers,
+    cursor_position: Res<CursorPosition>,
+    mouse: Res<Input<MouseButton>>,
+    mode: Res<ClickMode>,
+) {
+    if !matches!(mode.as_ref(), ClickMode::Move) {
+        held.0 = None;
+        return;
+    }
+
+    if let Some(location) = GridLocation::from_world(cursor_position.world_position) {
+        if mouse.just_pressed(MouseButton::Left) && !cursor_position.over_ui {
+            held.0 = layers
+                .get(TileLayer::Structure, &location)
+                .map(|_| location.clone());
+        }
+        if mouse.just_released(MouseButton::Left) {
+            if let Some(grabbed) = held.0.take() {
+                if grabbed != location {
+                    actions.push(PlayerAction::Move {
+                        grabbed,
+                        dropped: location,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drag_shapes() {
+        let start = GridLocation::new(2, 2);
+        let end = GridLocation::new(5, 3);
+
+        let line = DragShape::Line.cells(&start, &end);
+        assert_eq!(line.len(), 4);
+        assert!(line.iter().all(|cell| cell.y == 2));
+
+        assert_eq!(DragShape::Room.cells(&start, &end).len(), 8);
+        assert_eq!(DragShape::Area.cells(&end, &start).len(), 8);
+
+        let room = DragShape::Room.cells(&start, &GridLocation::new(6, 6));
+        assert_eq!(room.len(), 16);
+        assert!(!room.contains(&GridLocation::new(4, 4)));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+pub struct PopulationPlugin;
+
+impl Plugin for PopulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Population>()
+            .add_systems(SimulationTick, (roll_wanderers, resolve_arrival).chain())
+            .add_systems(Update, (answer_arrival_prompt, show_arrival_prompt).chain());
+    }
+}
+
+// Simulated seconds between chances for a wanderer to turn up
+const ARRIVAL_INTERVAL: f32 = 120.0;
+// How long a wanderer waits for an answer before moving on
+const ARRIVAL_PATIENCE: f32 = 60.0;
+
+// Someone at the edge of the map asking to join, rolled before the player decides
+pub struct Arrival {
+    pub name: Name,
+    pub traits: Traits,
+    pub skills: Skills,
+    pub edge: GridLocation,
+    waited: f32,
+}
+
+#[derive(Resource)]
+pub struct Population {
+    until_next: f32,
+    pub pending: Option<Arrival>,
+    // Set by the prompt's player action, resolved on the same tick
+    decision: Option<bool>,
+}
+
+impl Default for Population {
+    fn default() -> Self {
+        Self {
+            until_next: ARRIVAL_INTERVAL,
+            pending: None,
+            decision: None,
+        }
+    }
+}
+
+impl Population {
+    pub(crate) fn answer(&mut self, accepted: bool) {
+        if self.pending.is_some() {
+            self.decision = Some(accepted);
+        }
+    }
+}
+
+// Happier and richer colonies attract more people
+pub fn arrival_chance(average_mood: f32, wealth: u32) -> f64 {
+    (0.25 + (average_mood as f64 - 50.0) / 200.0 + wealth as f64 / 2000.0).clamp(0.05, 0.9)
+}
+
+#[derive(Component)]
+struct ArrivalPrompt;
+
+#[derive(Component)]
+struct ArrivalButton(bool);
+
+// Open cells on the map border connected to where the colony lives
+fn entry_points(
+    structures: &Grid<Structure>,
+    components: &ConnectedComponents<Structure>,
+    home: &GridLocation,
+) -> Vec<GridLocation> {
+    let last = grid_size() as u32 - 1;
+    (0..grid_size() as u32)
+        .flat_map(|i| {
+            [
+                GridLocation::new(i, 0),
+                GridLocation::new(i, last),
+                GridLocation::new(0, i),
+                GridLocation::new(last, i),
+            ]
+        })
+        .filter(|cell| !structures.occupied(cell) && components.in_same_component(cell, home))
+        .collect()
+}
+
+// Free cell next to the first bed, so arrivals walk into the colony rather than towards whoever
+// happens to be first in a query
+fn arrival_home(
+    beds: &Query<&GridLocation, With<Bed>>,
+    structures: &Grid<Structure>,
+) -> GridLocation {
+    let mut beds = beds.iter().collect::<Vec<_>>();
+    beds.sort_by_key(|bed| (bed.x, bed.y));
+    beds.iter()
+        .flat_map(|bed| bed.neighbors())
+        .find(|cell| cell.in_bounds() && !structures.occupied(cell))
+        .unwrap_or_else(spawn_point)
+}
+
+fn roll_wanderers(
+    mut population: ResMut<Population>,
+    mut rng: ResMut<SimRng>,
+    pawns: Query<&Mood, With<Pawn>>,
+    beds: Query<&GridLocation, With<Bed>>,
+    buildings: Query<&BuildCost>,
+    stockpile: Res<Stockpile>,
+    structures: Res<Grid<Structure>>,
+    components: Res<ConnectedComponents<Structure>>,
+    clock: Res<SimClock>,
+) {
+    population.until_next -= clock.delta_seconds();
+    if population.until_next > 0.0 {
+        return;
+    }
+    population.until_next = ARRIVAL_INTERVAL;
+
+    let count = pawns.iter().len();
+    if population.pending.is_some() || count >= beds.iter().len() {
+        return;
+    }
+    let average_mood = pawns.iter().map(|mood| mood.value).sum::<f32>() / count.max(1) as f32;
+    let wealth = stockpile.materials + buildings.iter().map(|cost| cost.0).sum::<u32>();
+
+    let rng = rng.stream(RngStream::Population);
+    if !rng.gen_bool(arrival_chance(average_mood, wealth)) {
+        return;
+    }
+    let home = arrival_home(&beds, &structures);
+    let edge = match entry_points(&structures, &components, &home).choose(rng) {
+        Some(edge) => edge.clone(),
+        None => {
+            warn!("Nowhere on the map edge leads to the colony");
+            return;
+        }
+    };
+
+    let arrival = Arrival {
+        name: random_name(rng),
+        traits: random_traits(rng),
+        skills: random_skills(rng),
+        edge,
+        waited: 0.0,
+    };
+    info!("{} asks to join the colony", arrival.name);
+    population.pending = Some(arrival);
+}
+
+fn resolve_arrival(
+    mut commands: Commands,
+    mut population: ResMut<Population>,
+    pawns: Query<(), With<Pawn>>,
+    beds: Query<&GridLocation, With<Bed>>,
+    structures: Res<Grid<Structure>>,
+    clock: Res<SimClock>,
+) {
+    let decision = population.decision.take();
+    let arrival = match &mut population.pending {
+        Some(arrival) => arrival,
+        None => return,
+    };
+    arrival.waited += clock.delta_seconds();
+    let accepted = match decision {
+        Some(accepted) => accepted,
+        None if arrival.waited > ARRIVAL_PATIENCE => false,
+        None => return,
+    };
+    let arrival = population.pending.take().unwrap();
+    if !accepted {
+        info!("{} moves on", arrival.name);
+        return;
+    }
+
+    // Beds may have been taken or torn down while they waited
+    if pawns.iter().len() >= beds.iter().len() {
+        info!("There's no bed left for {}, they move on", arrival.name);
+        return;
+    }
+
+    info!("{} joins the colony", arrival.name);
+    let pawn = spawn_pawn_with_identity(
+        &mut commands,
+        arrival.edge.as_vec2(),
+        (arrival.name, arrival.traits, arrival.skills),
+    );
+    let home = arrival_home(&beds, &structures);
+    spawn_optimized_pathfinding_task(&mut commands, &clock, pawn, &structures, arrival.edge, home);
+}
+
+// Y accepts and N rejects too, unless Ctrl is held for redo
+fn answer_arrival_prompt(
+    mut actions: ResMut<PlayerActions>,
+    population: Res<Population>,
+    buttons: Query<(&Interaction, &ArrivalButton), Changed<Interaction>>,
+    keyboard: Res<Input<KeyCode>>,
+) {
+    if population.pending.is_none() {
+        return;
+    }
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            actions.push(PlayerAction::AnswerArrival(button.0));
+        }
+    }
+    if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Y) {
+        actions.push(PlayerAction::AnswerArrival(true));
+    }
+    if keyboard.just_pressed(KeyCode::N) {
+        actions.push(PlayerAction::AnswerArrival(false));
+    }
+}
+
+fn show_arrival_prompt(
+    mut commands: Commands,
+    population: Res<Population>,
+    prompts: Query<Entity, With<ArrivalPrompt>>,
+) {
+    let arrival = match (&population.pending, prompts.get_single()) {
+        (Some(arrival), Err(_)) => arrival,
+        (None, Ok(prompt)) => {
+            commands.entity(prompt).despawn_recursive();
+            return;
+        }
+        _ => return,
+    };
+
+    let traits = if arrival.traits.0.is_empty() {
+        "no traits".to_string()
+    } else {
+        format!("{:?}", arrival.traits.0)
+    };
+    let message = format!(
+        "{} asks to join\n{}\nConstruction {} Cooking {}",
+        arrival.name,
+        traits,
+        arrival.skills.construction.level(),
+        arrival.skills.cooking.level()
+    );
+    commands
+        .spawn((
+            panel(Style {
+                top: Val::Px(48.0),
+                left: Val::Percent(40.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            }),
+            ArrivalPrompt,
+        ))
+        .with_children(|prompt| {
+            prompt.spawn(TextBundle::from_section(message, text_style()));
+            prompt
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|buttons| {
+                    for (accept, label) in [(true, "Accept (Y)"), (false, "Reject (N)")] {
+                        buttons
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::all(Val::Px(6.0)),
+                                        ..default()
+                                    },
+                                    background_color: BUTTON_COLOR.into(),
+                                    ..default()
+                                },
+                                ArrivalButton(accept),
+                            ))
+                            .with_children(|button| {
+                                button.spawn(TextBundle::from_section(label, text_style()));
+                            });
+                    }
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrivals_follow_mood_and_wealth() {
+        assert!(arrival_chance(80.0, 500) > arrival_chance(20.0, 500));
+        assert!(arrival_chance(50.0, 1000) > arrival_chance(50.0, 0));
+        assert_eq!(arrival_chance(0.0, 0), 0.05);
+        assert_eq!(arrival_chance(100.0, 100_000), 0.9);
+    }
+}
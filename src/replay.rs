@@ -0,0 +1,203 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::prelude::*;
+use bevy::app::AppExit;
+use serde::{Deserialize, Serialize};
+
+// Every session is recorded, F10 or closing the game writes it out. Start with
+// `--replay <file>` to feed a recording back into a fresh world
+pub struct ReplayPlugin {
+    replay: Option<PathBuf>,
+}
+
+impl ReplayPlugin {
+    pub fn from_args() -> Self {
+        let replay = std::env::args()
+            .skip_while(|arg| arg != "--replay")
+            .nth(1)
+            .map(PathBuf::from);
+        Self { replay }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let setup = ReplaySetup::from_args();
+        let state = match &self.replay {
+            Some(path) => match Replay::read(path) {
+                Ok(replay) if replay.setup != setup => {
+                    warn!(
+                        "Not replaying {:?}, it was recorded with {:?} but this run has {:?}",
+                        path, replay.setup, setup
+                    );
+                    ReplayState::Recording
+                }
+                Ok(replay) => {
+                    info!("Replaying {:?} with seed {}", path, replay.seed);
+                    app.insert_resource(SimRng::new(replay.seed))
+                        .insert_resource(PlayerActions::replaying());
+                    ReplayState::Playing { replay, next: 0 }
+                }
+                Err(err) => {
+                    warn!("Failed to read replay {:?}: {}", path, err);
+                    ReplayState::Recording
+                }
+            },
+            None => ReplayState::Recording,
+        };
+        app.insert_resource(state)
+            .insert_resource(setup)
+            .add_systems(TickBoundary, feed_replay.before(ApplyActions))
+            .add_systems(Last, write_replay);
+    }
+}
+
+const REPLAY_PATH: &str = "replays/last.ron";
+
+// The seed, what the world was started from and every player action with the tick it was
+// applied on. The simulation is deterministic between actions so nothing else has to be stored
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Replay {
+    seed: u64,
+    setup: ReplaySetup,
+    actions: Vec<(u64, PlayerAction)>,
+}
+
+// Everything from the command line that shapes the starting colony. A replay only plays back
+// into a run started the same way
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ReplaySetup {
+    grid_size: usize,
+    scenario: String,
+    // The scenario file as it was when the run started, later edits are recorded as actions
+    scenario_contents: Option<String>,
+    map: Option<String>,
+}
+
+impl ReplaySetup {
+    fn from_args() -> Self {
+        let scenario = scenario_path_from_args();
+        Self {
+            grid_size: grid_size(),
+            scenario_contents: fs::read_to_string(format!("assets/{}", scenario)).ok(),
+            scenario,
+            map: std::env::args().skip_while(|arg| arg != "--map").nth(1),
+        }
+    }
+}
+
+impl Replay {
+    fn read(path: &PathBuf) -> io::Result<Self> {
+        let serialized = fs::read_to_string(path)?;
+        ron::from_str(&serialized).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn write(&self, path: &str) -> io::Result<()> {
+        let serialized =
+            ron::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if let Some(directory) = PathBuf::from(path).parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, serialized)
+    }
+}
+
+#[derive(Resource)]
+pub enum ReplayState {
+    // The applied actions are kept by PlayerActions
+    Recording,
+    Playing { replay: Replay, next: usize },
+    Finished,
+}
+
+// Queues the actions recorded on this tick, in the order they were applied
+fn feed_replay(
+    mut state: ResMut<ReplayState>,
+    mut actions: ResMut<PlayerActions>,
+    clock: Res<SimClock>,
+) {
+    let (replay, next) = match state.as_mut() {
+        ReplayState::Playing { replay, next } => (replay, next),
+        _ => return,
+    };
+    while let Some((tick, action)) = replay.actions.get(*next) {
+        if *tick != clock.tick() {
+            return;
+        }
+        actions.push_replayed(action.clone());
+        *next += 1;
+    }
+    info!("Replay finished at tick {}", clock.tick());
+    actions.stop_replaying();
+    *state = ReplayState::Finished;
+}
+
+fn write_replay(
+    state: Res<ReplayState>,
+    setup: Res<ReplaySetup>,
+    actions: Res<PlayerActions>,
+    rng: Res<SimRng>,
+    keyboard: Res<Input<KeyCode>>,
+    mut exit: EventReader<AppExit>,
+) {
+    let closing = exit.iter().count() > 0;
+    if !keyboard.just_pressed(KeyCode::F10) && !closing {
+        return;
+    }
+    if let ReplayState::Recording = state.as_ref() {
+        let replay = Replay {
+            seed: rng.seed(),
+            setup: setup.clone(),
+            actions: actions.applied().to_vec(),
+        };
+        match replay.write(REPLAY_PATH) {
+            Ok(()) => info!("Wrote replay to {}", REPLAY_PATH),
+            Err(err) => warn!("Failed to write replay: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_round_trips() {
+        let replay = Replay {
+            seed: 7,
+            setup: ReplaySetup {
+                grid_size: 64,
+                scenario: "scenarios/test.scenario.ron".to_string(),
+                scenario_contents: Some("(name: \"Test\")".to_string()),
+                map: None,
+            },
+            actions: vec![
+                (
+                    12,
+                    PlayerAction::Build {
+                        mode: ClickMode::BuildWall,
+                        rotation: Rotation::default(),
+                        cells: vec![GridLocation::new(3, 4), GridLocation::new(4, 4)],
+                    },
+                ),
+                (30, PlayerAction::Undo),
+                (30, PlayerAction::Explode(GridLocation::new(5, 5))),
+                (
+                    41,
+                    PlayerAction::LoadScenario(Box::new(Scenario {
+                        name: "Reloaded".to_string(),
+                        map: MapSource::Generated(MapSettings::default()),
+                        outline: true,
+                        materials: 10,
+                        buildings: Vec::new(),
+                        hazards: Vec::new(),
+                        pawns: vec![ScenarioPawn::default()],
+                        objectives: Objectives::default(),
+                    })),
+                ),
+            ],
+        };
+        let serialized = ron::to_string(&replay).unwrap();
+        assert_eq!(ron::from_str::<Replay>(&serialized).unwrap(), replay);
+    }
+}
@@ -0,0 +1,542 @@
+use std::{fs, path::PathBuf};
+
+use crate::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::reflect::TypePath;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+
+// Start with `--scenario <file>` to pick one from the assets folder. Editing the active scenario
+// while the game runs rebuilds the colony from it
+pub struct ScenarioPlugin {
+    path: String,
+}
+
+impl ScenarioPlugin {
+    pub fn from_args() -> Self {
+        Self {
+            path: scenario_path_from_args(),
+        }
+    }
+}
+
+pub(crate) fn scenario_path_from_args() -> String {
+    std::env::args()
+        .skip_while(|arg| arg != "--scenario")
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_SCENARIO.to_string())
+}
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        // Read up front so the colony exists on the first frame, replays depend on that.
+        // Platforms without a plain assets folder wait for the asset server instead
+        let scenario = fs::read_to_string(format!("assets/{}", self.path))
+            .ok()
+            .and_then(|text| match ron::from_str::<Scenario>(&text) {
+                Ok(scenario) => Some(scenario),
+                Err(err) => {
+                    warn!("Failed to parse scenario {}: {}", self.path, err);
+                    None
+                }
+            });
+        app.add_asset::<Scenario>()
+            .init_asset_loader::<ScenarioLoader>()
+            .insert_resource(ActiveScenario {
+                path: self.path.clone(),
+                handle: Handle::default(),
+                dirty: scenario.is_some(),
+                scenario,
+                unplaced: Vec::new(),
+            })
+            .init_resource::<ScenarioOutcome>()
+            .add_systems(Startup, load_scenario)
+            .add_systems(PreUpdate, (watch_scenario, apply_scenario).chain())
+            // Reloads are player actions so replays see them, the colony is rebuilt right after
+            // the action lands
+            .add_systems(PostUpdate, apply_scenario.after(ApplyActions))
+            .add_systems(
+                TickBoundary,
+                (
+                    (apply_scenario, apply_deferred)
+                        .chain()
+                        .after(ApplyActions)
+                        .before(GridSync),
+                    place_scenario_buildings.after(GridSync),
+                ),
+            )
+            .add_systems(PostUpdate, check_objectives);
+    }
+}
+
+const DEFAULT_SCENARIO: &str = "scenarios/default.scenario.ron";
+
+#[derive(Serialize, Deserialize, TypeUuid, TypePath, Clone, PartialEq, Debug)]
+#[uuid = "6f0a3c51-2d1e-4b7a-9c55-8e4d2b0f7a13"]
+pub struct Scenario {
+    pub name: String,
+    pub map: MapSource,
+    #[serde(default = "default_outline")]
+    pub outline: bool,
+    #[serde(default)]
+    pub materials: u32,
+    #[serde(default)]
+    pub buildings: Vec<ScenarioBuilding>,
+    #[serde(default)]
+    pub hazards: Vec<ScenarioHazard>,
+    #[serde(default)]
+    pub pawns: Vec<ScenarioPawn>,
+    #[serde(default)]
+    pub objectives: Objectives,
+}
+
+fn default_outline() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum MapSource {
+    Generated(MapSettings),
+    // An ASCII or PNG map, relative to the working directory like `--map`
+    File(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum BuildingKind {
+    Wall,
+    FoodMachine,
+    Kitchen,
+    Bed,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ScenarioBuilding {
+    pub kind: BuildingKind,
+    pub location: (u32, u32),
+    #[serde(default)]
+    pub rotation: Rotation,
+}
+
+// Damages structures in and next to its cell for as long as the scenario runs
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ScenarioHazard {
+    pub location: (u32, u32),
+    pub damage_per_second: f32,
+}
+
+// Pawns without a location take turns at the map's spawn points, names and traits left out are rolled
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct ScenarioPawn {
+    pub count: u32,
+    pub location: Option<(u32, u32)>,
+    pub name: Option<String>,
+    pub traits: Option<Vec<Trait>>,
+    pub hunger: f32,
+    pub recreation: f32,
+}
+
+impl Default for ScenarioPawn {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            location: None,
+            name: None,
+            traits: None,
+            hunger: 100.0,
+            recreation: 100.0,
+        }
+    }
+}
+
+// Won once every win condition holds, lost as soon as any lose condition does
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct Objectives {
+    pub win: Vec<Objective>,
+    pub lose: Vec<Objective>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Objective {
+    // Simulated seconds
+    Survive(f32),
+    PopulationAtLeast(u32),
+    PopulationBelow(u32),
+    FoodMachinesAtLeast(u32),
+    MaterialsAtLeast(u32),
+    AverageHungerBelow(f32),
+}
+
+struct ColonyStats {
+    seconds: f32,
+    population: u32,
+    food_machines: u32,
+    materials: u32,
+    average_hunger: f32,
+}
+
+impl Objective {
+    fn met(&self, stats: &ColonyStats) -> bool {
+        match self {
+            Objective::Survive(seconds) => stats.seconds >= *seconds,
+            Objective::PopulationAtLeast(count) => stats.population >= *count,
+            Objective::PopulationBelow(count) => stats.population < *count,
+            Objective::FoodMachinesAtLeast(count) => stats.food_machines >= *count,
+            Objective::MaterialsAtLeast(count) => stats.materials >= *count,
+            Objective::AverageHungerBelow(hunger) => stats.average_hunger < *hunger,
+        }
+    }
+}
+
+impl Objectives {
+    fn outcome(&self, stats: &ColonyStats) -> ScenarioOutcome {
+        if self.lose.iter().any(|objective| objective.met(stats)) {
+            ScenarioOutcome::Lost
+        } else if !self.win.is_empty() && self.win.iter().all(|objective| objective.met(stats)) {
+            ScenarioOutcome::Won
+        } else {
+            ScenarioOutcome::Playing
+        }
+    }
+}
+
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScenarioOutcome {
+    #[default]
+    Playing,
+    Won,
+    Lost,
+}
+
+#[derive(Resource)]
+pub struct ActiveScenario {
+    path: String,
+    handle: Handle<Scenario>,
+    scenario: Option<Scenario>,
+    // Set when the scenario changes, the colony is rebuilt on the next PreUpdate
+    dirty: bool,
+    // Scenario buildings wait for the next tick boundary, once the grids hold the new map
+    unplaced: Vec<Blueprint>,
+}
+
+#[derive(Default)]
+struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let scenario: Scenario = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(scenario));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+impl ActiveScenario {
+    // Rebuilds the colony from it on the next chance
+    pub(crate) fn load(&mut self, scenario: Scenario) {
+        info!("Loading scenario {}", scenario.name);
+        self.scenario = Some(scenario);
+        self.dirty = true;
+    }
+}
+
+// Win or lose message, cleared when the scenario is applied again
+#[derive(Component)]
+struct ScenarioBanner;
+
+fn load_scenario(mut active: ResMut<ActiveScenario>, asset_server: Res<AssetServer>) {
+    active.handle = asset_server.load(active.path.as_str());
+}
+
+// The first load is part of starting the game, later edits are queued as actions
+fn watch_scenario(
+    mut active: ResMut<ActiveScenario>,
+    mut events: EventReader<AssetEvent<Scenario>>,
+    scenarios: Res<Assets<Scenario>>,
+    mut actions: ResMut<PlayerActions>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != active.handle {
+            continue;
+        }
+        let scenario = match scenarios.get(handle) {
+            Some(scenario) => scenario.clone(),
+            None => continue,
+        };
+        match event {
+            AssetEvent::Created { .. } if active.scenario.is_none() => active.load(scenario),
+            AssetEvent::Modified { .. } => {
+                actions.push(PlayerAction::LoadScenario(Box::new(scenario)))
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_scenario(
+    mut commands: Commands,
+    mut active: ResMut<ActiveScenario>,
+    mut rng: ResMut<SimRng>,
+    mut stockpile: ResMut<Stockpile>,
+    mut history: ResMut<EditHistory>,
+    mut selected: ResMut<SelectedPawn>,
+    mut outcome: ResMut<ScenarioOutcome>,
+    mut population: ResMut<Population>,
+    mut clock: ResMut<SimClock>,
+    loaded: Option<Res<LoadedMap>>,
+    existing: Query<
+        Entity,
+        Or<(
+            With<Pawn>,
+            With<Corpse>,
+            With<Structure>,
+            With<WallSprite>,
+            With<Hazard>,
+            With<ScenarioBanner>,
+        )>,
+    >,
+) {
+    if !active.dirty {
+        return;
+    }
+    active.dirty = false;
+    let active = active.as_mut();
+    let scenario = match &active.scenario {
+        Some(scenario) => scenario,
+        None => return,
+    };
+
+    // A reload starts the same run over rather than continuing it
+    for entity in &existing {
+        commands.entity(entity).despawn_recursive();
+    }
+    *rng = SimRng::new(rng.seed());
+    *history = EditHistory::default();
+    selected.0 = None;
+    *outcome = ScenarioOutcome::Playing;
+    *population = Population::default();
+    clock.restart();
+    stockpile.materials = scenario.materials;
+
+    // `--map` wins over whatever the scenario asks for
+    let spawns = match (&loaded, &scenario.map) {
+        (Some(loaded), _) => {
+            loaded.0.spawn(&mut commands);
+            loaded.0.spawns.clone()
+        }
+        (None, MapSource::Generated(settings)) => {
+            let map = generate_map(settings, rng.stream(RngStream::MapGeneration));
+            map.spawn(&mut commands);
+            vec![map.spawn]
+        }
+        (None, MapSource::File(path)) => {
+            match load_map(&PathBuf::from(path), &PathBuf::from(PALETTE_PATH)) {
+                Ok(map) => {
+                    map.spawn(&mut commands);
+                    map.spawns
+                }
+                Err(err) => {
+                    warn!("Failed to load map {}: {}", path, err);
+                    Vec::new()
+                }
+            }
+        }
+    };
+
+    if scenario.outline {
+        spawn_outline(&mut commands);
+    }
+    active.unplaced = scenario
+        .buildings
+        .iter()
+        .filter_map(|building| {
+            let mode = match building.kind {
+                BuildingKind::Wall => ClickMode::BuildWall,
+                BuildingKind::FoodMachine => ClickMode::BuildFoodMachine,
+                BuildingKind::Kitchen => ClickMode::BuildKitchen,
+                BuildingKind::Bed => ClickMode::BuildBed,
+            };
+            let (x, y) = building.location;
+            mode.blueprint(GridLocation::new(x, y), building.rotation)
+        })
+        .collect();
+
+    for hazard in &scenario.hazards {
+        let (x, y) = hazard.location;
+        spawn_hazard(
+            &mut commands,
+            GridLocation::new(x, y),
+            hazard.damage_per_second,
+        );
+    }
+
+    // A roster-less scenario gets one default pawn per spawn point
+    let default_roster = vec![ScenarioPawn {
+        count: spawns.len().max(1) as u32,
+        ..default()
+    }];
+    let roster = if scenario.pawns.is_empty() {
+        &default_roster
+    } else {
+        &scenario.pawns
+    };
+    let mut spawn_points = spawns.iter().cycle();
+    for pawn in roster {
+        for _ in 0..pawn.count {
+            let location = match pawn.location {
+                Some((x, y)) => GridLocation::new(x, y),
+                None => spawn_points.next().cloned().unwrap_or_else(spawn_point),
+            };
+            let entity = spawn_pawn(&mut commands, &mut rng, location.as_vec2());
+            commands.entity(entity).insert((
+                Hunger { value: pawn.hunger },
+                Recreation {
+                    value: pawn.recreation,
+                },
+            ));
+            if let Some(name) = &pawn.name {
+                commands.entity(entity).insert(Name::new(name.clone()));
+            }
+            if let Some(traits) = &pawn.traits {
+                commands.entity(entity).insert(Traits(traits.clone()));
+            }
+        }
+    }
+}
+
+// Buildings overlapping the map, each other or the edge would corrupt the grid, so they are skipped
+fn place_scenario_buildings(
+    mut commands: Commands,
+    mut active: ResMut<ActiveScenario>,
+    layers: TileLayers,
+) {
+    if active.unplaced.is_empty() {
+        return;
+    }
+    let mut claimed = Vec::new();
+    for blueprint in std::mem::take(&mut active.unplaced) {
+        let cells = blueprint.cells();
+        if !blueprint.can_place(&layers) || cells.iter().any(|cell| claimed.contains(cell)) {
+            warn!(
+                "Scenario building at {:?} doesn't fit, skipping it",
+                cells[0].0
+            );
+            continue;
+        }
+        claimed.extend(cells);
+        blueprint.spawn(&mut commands);
+    }
+}
+
+fn spawn_outline(commands: &mut Commands) {
+    for i in 0..grid_size() {
+        spawn_outline_wall(commands, i as f32, -1.0);
+        spawn_outline_wall(commands, i as f32, grid_size() as f32);
+    }
+    for j in -1..(grid_size() as i32 + 1) {
+        spawn_outline_wall(commands, -1.0, j as f32);
+        spawn_outline_wall(commands, grid_size() as f32, j as f32);
+    }
+}
+
+fn spawn_outline_wall(commands: &mut Commands, x: f32, y: f32) {
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_xyz(x, y, 0.0)),
+        WallSprite::Outline,
+    ));
+}
+
+fn check_objectives(
+    mut commands: Commands,
+    mut outcome: ResMut<ScenarioOutcome>,
+    mut clock: ResMut<SimClock>,
+    active: Res<ActiveScenario>,
+    pawns: Query<&Hunger, With<Pawn>>,
+    corpses: Query<(), With<Corpse>>,
+    food_machines: Query<(), With<FoodMachine>>,
+    stockpile: Res<Stockpile>,
+) {
+    let scenario = match (&active.scenario, *outcome) {
+        (Some(scenario), ScenarioOutcome::Playing) => scenario,
+        _ => return,
+    };
+    let population = pawns.iter().len() as u32;
+    let stats = ColonyStats {
+        seconds: clock.elapsed_seconds(),
+        population,
+        food_machines: food_machines.iter().len() as u32,
+        materials: stockpile.materials,
+        average_hunger: pawns.iter().map(|hunger| hunger.value).sum::<f32>()
+            / population.max(1) as f32,
+    };
+
+    // Whatever the objectives say, a colony with nobody left alive is over
+    *outcome = if population == 0 && !corpses.is_empty() {
+        ScenarioOutcome::Lost
+    } else {
+        scenario.objectives.outcome(&stats)
+    };
+    let message = match *outcome {
+        ScenarioOutcome::Playing => return,
+        ScenarioOutcome::Won => format!("{} complete", scenario.name),
+        ScenarioOutcome::Lost => format!("{} failed", scenario.name),
+    };
+    info!("{}", message);
+    clock.paused = true;
+    commands
+        .spawn((
+            panel(Style {
+                top: Val::Px(8.0),
+                left: Val::Percent(45.0),
+                ..default()
+            }),
+            ScenarioBanner,
+        ))
+        .with_children(|banner| {
+            banner.spawn(TextBundle::from_section(message, text_style()));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_scenario_parses_and_objectives_resolve() {
+        let text = fs::read_to_string(format!("assets/{}", DEFAULT_SCENARIO)).unwrap();
+        let scenario: Scenario = ron::from_str(&text).unwrap();
+        assert!(matches!(scenario.map, MapSource::Generated(_)));
+        assert_eq!(scenario.hazards.len(), 1);
+
+        let objectives: Objectives = ron::from_str(
+            "(win: [Survive(60.0), PopulationAtLeast(5)], lose: [PopulationBelow(1)])",
+        )
+        .unwrap();
+        let mut stats = ColonyStats {
+            seconds: 10.0,
+            population: 6,
+            food_machines: 0,
+            materials: 0,
+            average_hunger: 100.0,
+        };
+        assert_eq!(objectives.outcome(&stats), ScenarioOutcome::Playing);
+        stats.seconds = 60.0;
+        assert_eq!(objectives.outcome(&stats), ScenarioOutcome::Won);
+        stats.population = 0;
+        assert_eq!(objectives.outcome(&stats), ScenarioOutcome::Lost);
+    }
+}
@@ -4,6 +4,8 @@ use crate::prelude::*;
 #[derive(Resource, Default)]
 pub struct CursorPosition {
     pub world_position: Vec2,
+    // From the top left of the window, same as UI positions
+    pub screen_position: Vec2,
     pub over_ui: bool,
 }
 
@@ -11,6 +13,7 @@ pub fn update_cursor(
     mut cursor: ResMut<CursorPosition>,
     windows: Query<&Window>,
     camera: Query<(&Camera, &GlobalTransform)>,
+    interactions: Query<&Interaction, With<Node>>,
 ) {
     let window = windows.single();
     let (camera, transform) = camera.single();
@@ -22,5 +25,10 @@ pub fn update_cursor(
             .origin
             .truncate();
         cursor.world_position = world_position;
+        cursor.screen_position = screen_position;
     }
+    // HUD panels and buttons all carry an Interaction, anything but None means the cursor is on one
+    cursor.over_ui = interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
 }
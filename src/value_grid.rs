@@ -0,0 +1,154 @@
+use std::ops::{Index, IndexMut};
+
+use crate::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+// Dense per cell data (floor types, costs, temperature...) that doesn't need an entity per cell
+#[derive(Resource, Clone)]
+pub struct ValueGrid<V: Clone> {
+    storage: ChunkedStorage<V>,
+}
+
+impl<V: Clone> ValueGrid<V> {
+    // Chunks are only allocated once a cell in them is written
+    pub fn new(value: V) -> Self {
+        Self {
+            storage: ChunkedStorage::new(value),
+        }
+    }
+
+    pub fn fill(&mut self, value: V) {
+        self.storage.clear(value);
+    }
+
+    pub fn from_fn(mut f: impl FnMut(&GridLocation) -> V) -> Self
+    where
+        V: Default,
+    {
+        let mut grid = Self::default();
+        for (location, value) in grid.iter_mut() {
+            *value = f(&location);
+        }
+        grid
+    }
+
+    pub fn get(&self, location: &GridLocation) -> Option<&V> {
+        location.in_bounds().then(|| &self[location])
+    }
+
+    pub fn get_mut(&mut self, location: &GridLocation) -> Option<&mut V> {
+        if location.in_bounds() {
+            Some(&mut self[location])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GridLocation, &V)> + '_ {
+        all_points()
+            .into_iter()
+            .map(|location| (location.clone(), self.storage.get(&location)))
+    }
+
+    // Allocates every chunk so all cells can be handed out
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (GridLocation, &mut V)> + '_ {
+        self.storage.allocate_all();
+        self.storage
+            .iter_mut()
+            .filter(|(location, _)| location.in_bounds())
+    }
+
+    pub fn neighbors(&self, location: &GridLocation) -> impl Iterator<Item = (GridLocation, &V)> {
+        location.neighbors().into_iter().map(|neighbor| {
+            let value = &self[&neighbor];
+            (neighbor, value)
+        })
+    }
+}
+
+impl<V> ChunkedGrid for ValueGrid<V>
+where
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    type Value = V;
+
+    fn storage(&self) -> &ChunkedStorage<V> {
+        &self.storage
+    }
+
+    fn storage_mut(&mut self) -> &mut ChunkedStorage<V> {
+        &mut self.storage
+    }
+}
+
+impl<V: Clone + Default> Default for ValueGrid<V> {
+    fn default() -> Self {
+        Self::new(V::default())
+    }
+}
+
+impl<V: Clone> Index<&GridLocation> for ValueGrid<V> {
+    type Output = V;
+
+    fn index(&self, index: &GridLocation) -> &Self::Output {
+        assert!(index.in_bounds(), "{:?} is not in the grid", index);
+        self.storage.get(index)
+    }
+}
+
+impl<V: Clone> IndexMut<&GridLocation> for ValueGrid<V> {
+    fn index_mut(&mut self, index: &GridLocation) -> &mut Self::Output {
+        assert!(index.in_bounds(), "{:?} is not in the grid", index);
+        self.storage.get_mut(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_follow_locations() {
+        let mut grid = ValueGrid::new(0.0);
+        let location = GridLocation::new(3, 7);
+        grid[&location] = 12.5;
+
+        assert_eq!(grid.get(&location), Some(&12.5));
+        assert_eq!(grid.get(&GridLocation(IVec2::new(-1, 0))), None);
+        assert_eq!(
+            grid.iter().find(|(_, value)| **value > 0.0).unwrap().0,
+            location
+        );
+        assert_eq!(grid.neighbors(&GridLocation::new(0, 0)).count(), 2);
+    }
+
+    #[test]
+    fn chunks_round_trip_through_disk() {
+        // Unique per run so parallel test processes don't share chunk files
+        let directory = std::env::temp_dir().join(format!(
+            "value_grid_chunks_{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let mut grid = ValueGrid::new(0u8);
+        let location = GridLocation::new(40, 70);
+        let written = GridLocation::new(41, 70);
+        grid[&location] = 3;
+        grid[&written] = 1;
+
+        let chunk = location.chunk();
+        grid.storage_mut().unload(chunk, &directory).unwrap();
+        assert_eq!(grid[&location], 0);
+        assert!(grid.storage().is_unloaded(&chunk));
+
+        // Written while on disk, must survive the load
+        grid[&written] = 5;
+        grid.storage_mut().load(chunk, &directory).unwrap();
+        assert_eq!(grid[&location], 3);
+        assert_eq!(grid[&written], 5);
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}